@@ -0,0 +1,349 @@
+//! Port of the detection rules of node's `cjs-module-lexer`, which decides the
+//! named exports an ES module sees when it imports a CommonJS module.
+//!
+//! The lexer works on source text and only understands a handful of shapes.
+//! Here the same shapes are matched on the emitted AST, so anything that
+//! passes these rules is visible to `import { name } from './dist/index.js'`.
+
+use std::collections::HashSet;
+
+use swc_atoms::JsWord;
+use swc_ecma_ast::*;
+use swc_ecma_visit::{Visit, VisitWith};
+
+#[derive(Default)]
+pub(crate) struct CjsModuleLexer {
+    pub(crate) exports: HashSet<JsWord>,
+    pub(crate) reexports: HashSet<JsWord>
+}
+
+impl CjsModuleLexer {
+    pub(crate) fn detect(module: &Module) -> CjsModuleLexer {
+        let mut lexer = CjsModuleLexer::default();
+        module.visit_with(&mut lexer);
+
+        lexer
+    }
+
+    // `module.exports = { a, b: c, 'd': e, ...require('x') }` stops at the
+    // first property of any other shape.
+    fn detect_object_literal_exports(&mut self, object: &ObjectLit) {
+        for property in &object.props {
+            match property {
+                PropOrSpread::Spread(spread) => match get_require_specifier(&spread.expr) {
+                    Some(specifier) => {
+                        self.reexports.insert(specifier);
+                    },
+                    None => return
+                },
+                PropOrSpread::Prop(prop) => match &**prop {
+                    Prop::Shorthand(ident) => {
+                        self.exports.insert(ident.sym.clone());
+                    },
+                    Prop::KeyValue(KeyValueProp { key, value }) if value.is_ident() => match key {
+                        PropName::Ident(ident) => {
+                            self.exports.insert(ident.sym.clone());
+                        },
+                        PropName::Str(str) => {
+                            self.exports.insert(str.value.clone());
+                        },
+                        _ => return
+                    },
+                    _ => return
+                }
+            }
+        }
+    }
+}
+
+impl Visit for CjsModuleLexer {
+    fn visit_assign_expr(&mut self, assignment: &AssignExpr) {
+        let target = match &assignment.left {
+            PatOrExpr::Expr(target) => Some(&**target),
+            PatOrExpr::Pat(target) => target.as_expr().map(|target| &**target)
+        };
+
+        if let Some(target) = target {
+            if let Some(export_name) = get_exports_property_name(target) {
+                self.exports.insert(export_name);
+            } else if is_exports_object(target) && !target.is_ident() {
+                match &*assignment.right {
+                    Expr::Object(object) => self.detect_object_literal_exports(object),
+                    right => {
+                        if let Some(specifier) = get_require_specifier(right) {
+                            self.reexports.insert(specifier);
+                        }
+                    }
+                }
+            }
+        }
+
+        assignment.visit_children_with(self);
+    }
+
+    fn visit_call_expr(&mut self, call_expression: &CallExpr) {
+        if let Some(export_name) = get_define_property_export_name(call_expression) {
+            self.exports.insert(export_name);
+        }
+
+        // __exportStar(require('x'), exports) and __export(require('x'))
+        let is_export_star_helper = call_expression.callee
+            .as_expr()
+            .and_then(|callee| callee.as_ident())
+            .map_or(false, |callee| &*callee.sym == "__exportStar" || &*callee.sym == "__export");
+
+        if is_export_star_helper {
+            let specifier = call_expression.args
+                .first()
+                .filter(|argument| argument.spread.is_none())
+                .and_then(|argument| get_require_specifier(&argument.expr));
+
+            if let Some(specifier) = specifier {
+                self.reexports.insert(specifier);
+            }
+        }
+
+        call_expression.visit_children_with(self);
+    }
+}
+
+fn is_identifier(expr: &Expr, name: &str) -> bool {
+    expr.as_ident().map_or(false, |ident| &*ident.sym == name)
+}
+
+fn get_string_literal(expr: &Expr) -> Option<JsWord> {
+    match expr {
+        Expr::Lit(Lit::Str(str)) => Some(str.value.clone()),
+        _ => None
+    }
+}
+
+// Only `.name` and `['name']` are understood; anything computed is skipped.
+fn get_member_property_name(property: &MemberProp) -> Option<JsWord> {
+    match property {
+        MemberProp::Ident(ident) => Some(ident.sym.clone()),
+        MemberProp::Computed(computed) => get_string_literal(&computed.expr),
+        MemberProp::PrivateName(_) => None
+    }
+}
+
+// `exports` or `module.exports`
+fn is_exports_object(expr: &Expr) -> bool {
+    match expr {
+        Expr::Ident(ident) => &*ident.sym == "exports",
+        Expr::Member(member) => {
+            is_identifier(&member.obj, "module")
+                && get_member_property_name(&member.prop).map_or(false, |name| &*name == "exports")
+        },
+        _ => false
+    }
+}
+
+// `exports.name`, `exports['name']` or `module.exports.name`
+fn get_exports_property_name(expr: &Expr) -> Option<JsWord> {
+    let member = expr.as_member()?;
+
+    if !is_exports_object(&member.obj) {
+        return None;
+    }
+
+    get_member_property_name(&member.prop)
+}
+
+fn get_require_specifier(expr: &Expr) -> Option<JsWord> {
+    let call_expression = expr.as_call()?;
+
+    if !call_expression.callee.as_expr().map_or(false, |callee| is_identifier(callee, "require")) {
+        return None;
+    }
+
+    match call_expression.args.as_slice() {
+        [ExprOrSpread { spread: None, expr }] => get_string_literal(expr),
+        _ => None
+    }
+}
+
+// The getter body must be exactly `return id;`, `return id.id;` or
+// `return id['str'];`, so the lexer knows reading it has no side effects.
+fn is_safe_getter_body(function: &Function) -> bool {
+    if !function.params.is_empty() || function.is_async || function.is_generator {
+        return false;
+    }
+
+    let statements = match &function.body {
+        Some(body) => &body.stmts,
+        None => return false
+    };
+
+    let returned_value = match statements.as_slice() {
+        [Stmt::Return(ReturnStmt { arg: Some(returned_value), .. })] => returned_value,
+        _ => return false
+    };
+
+    match &**returned_value {
+        Expr::Ident(_) => true,
+        Expr::Member(member) => member.obj.is_ident() && match &member.prop {
+            MemberProp::Ident(_) => true,
+            MemberProp::Computed(computed) => get_string_literal(&computed.expr).is_some(),
+            MemberProp::PrivateName(_) => false
+        },
+        _ => false
+    }
+}
+
+fn is_prop_key(key: &PropName, name: &str) -> bool {
+    match key {
+        PropName::Ident(ident) => &*ident.sym == name,
+        _ => false
+    }
+}
+
+// Object.defineProperty(exports, 'name', { enumerable: true, get: function () { return id.id; } })
+// Object.defineProperty(exports, 'name', { enumerable: true, get () { return id.id; } })
+// Object.defineProperty(exports, 'name', { value: ... })
+fn get_define_property_export_name(call_expression: &CallExpr) -> Option<JsWord> {
+    let callee = call_expression.callee.as_expr()?.as_member()?;
+
+    if !is_identifier(&callee.obj, "Object") || get_member_property_name(&callee.prop).as_deref() != Some("defineProperty") {
+        return None;
+    }
+
+    let (target, export_name, descriptor) = match call_expression.args.as_slice() {
+        [target, export_name, descriptor] if target.spread.is_none() && export_name.spread.is_none() && descriptor.spread.is_none() => {
+            (&target.expr, &export_name.expr, &descriptor.expr)
+        },
+        _ => return None
+    };
+
+    if !is_exports_object(target) {
+        return None;
+    }
+
+    let export_name = get_string_literal(export_name)?;
+    let mut properties = descriptor.as_object()?.props.iter().peekable();
+
+    let is_enumerable_property = |property: &PropOrSpread| match property {
+        PropOrSpread::Prop(prop) => match &**prop {
+            Prop::KeyValue(KeyValueProp { key, value }) => {
+                is_prop_key(key, "enumerable") && matches!(&**value, Expr::Lit(Lit::Bool(Bool { value: true, .. })))
+            },
+            _ => false
+        },
+        PropOrSpread::Spread(_) => false
+    };
+
+    if properties.peek().map_or(false, |property| is_enumerable_property(property)) {
+        properties.next();
+    }
+
+    let property = properties.next()?.as_prop()?;
+    let is_last_property = properties.next().is_none();
+
+    let is_detected = match &**property {
+        Prop::KeyValue(KeyValueProp { key, .. }) if is_prop_key(key, "value") => true,
+        Prop::KeyValue(KeyValueProp { key, value }) if is_prop_key(key, "get") => {
+            is_last_property && value.as_fn_expr().map_or(false, |function_expression| is_safe_getter_body(&function_expression.function))
+        },
+        Prop::Method(MethodProp { key, function }) if is_prop_key(key, "get") => is_last_property && is_safe_getter_body(function),
+        _ => false
+    };
+
+    match is_detected {
+        true => Some(export_name),
+        false => None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use swc_core::common::{sync::Lrc, FileName, SourceMap};
+    use swc_ecma_parser::{parse_file_as_module, Syntax};
+
+    use super::*;
+
+    fn detect(source: &str) -> CjsModuleLexer {
+        let source_map: Lrc<SourceMap> = Default::default();
+        let source_file = source_map.new_source_file(FileName::Anon, source.into());
+
+        let module = parse_file_as_module(&source_file, Syntax::default(), EsVersion::latest(), None, &mut vec![])
+            .expect("test source should parse");
+
+        CjsModuleLexer::detect(&module)
+    }
+
+    fn detected_exports(source: &str) -> Vec<String> {
+        let mut exports: Vec<String> = detect(source).exports.into_iter().map(|name| name.to_string()).collect();
+        exports.sort();
+
+        exports
+    }
+
+    #[test]
+    fn detects_export_assignments() {
+        assert_eq!(
+            detected_exports(r#"exports.a = 1; exports["b-c"] = 2; module.exports.d = 3; module.exports["e"] = 4;"#),
+            vec!["a", "b-c", "d", "e"]
+        );
+    }
+
+    #[test]
+    fn rejects_computed_export_assignments() {
+        assert!(detected_exports("exports[name] = 1; exports[`a`] = 2; other.a = 3;").is_empty());
+    }
+
+    #[test]
+    fn detects_define_property_values_and_safe_getters() {
+        assert_eq!(
+            detected_exports(r#"
+                Object.defineProperty(exports, "a", { value: 1 });
+                Object.defineProperty(exports, "b", { enumerable: true, get: function () { return _b.b; } });
+                Object.defineProperty(exports, "c", { enumerable: true, get() { return _c["c"]; } });
+                Object.defineProperty(module.exports, "d", { get: function d() { return d; } });
+            "#),
+            vec!["a", "b", "c", "d"]
+        );
+    }
+
+    #[test]
+    fn rejects_unsafe_getter_bodies() {
+        assert!(detected_exports(r#"
+            Object.defineProperty(exports, "a", { enumerable: true, get: function () { return _a.b.c; } });
+            Object.defineProperty(exports, "b", { enumerable: true, get: function () { return load(); } });
+            Object.defineProperty(exports, "c", { enumerable: true, get: function () { track(); return c; } });
+            Object.defineProperty(exports, "d", { enumerable: true, get: () => d });
+            Object.defineProperty(exports, "e", { enumerable: true, get: function (value) { return e; } });
+        "#).is_empty());
+    }
+
+    #[test]
+    fn rejects_getters_that_are_not_the_last_property() {
+        assert!(detected_exports(r#"
+            Object.defineProperty(exports, "a", { enumerable: true, get: function () { return a; }, configurable: true });
+            Object.defineProperty(exports, "b", { configurable: true, get: function () { return b; } });
+            Object.defineProperty(exports, c, { value: 1 });
+        "#).is_empty());
+    }
+
+    #[test]
+    fn detects_object_literal_exports_up_to_the_first_unsupported_property() {
+        assert_eq!(
+            detected_exports(r#"module.exports = { a, b: c, "d": e, f: "f", g };"#),
+            vec!["a", "b", "d"]
+        );
+    }
+
+    #[test]
+    fn detects_reexports() {
+        let lexer = detect(r#"
+            __exportStar(require("./a"), exports);
+            __export(require("./b"));
+            module.exports = require("./c");
+            __exportStar(load("./d"), exports);
+        "#);
+
+        let mut reexports: Vec<String> = lexer.reexports.into_iter().map(|name| name.to_string()).collect();
+        reexports.sort();
+
+        assert_eq!(reexports, vec!["./a", "./b", "./c"]);
+    }
+}
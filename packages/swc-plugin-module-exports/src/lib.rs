@@ -1,24 +1,72 @@
 #![allow(clippy::not_unsafe_ptr_arg_deref)]
 
+use std::collections::{HashMap, HashSet};
+
+use serde::Deserialize;
 use swc_atoms::{JsWord, Atom};
-use swc_core::common::DUMMY_SP;
+use swc_core::common::{errors::HANDLER, Span, Spanned, DUMMY_SP};
 use swc_core::plugin::proxies::TransformPluginProgramMetadata;
-use swc_ecma_visit::{VisitMut, VisitMutWith};
+use swc_ecma_visit::{Visit, VisitMut, VisitMutWith, VisitWith};
 use swc_ecma_ast::*;
 use swc_plugin_macro::plugin_transform;
 
+mod cjs_module_lexer;
+
+use cjs_module_lexer::CjsModuleLexer;
+
 const DEFAULT_MODULE_EXPORT_ALL_STRUCT: ModuleExportAll = ModuleExportAll {
     has_writed_name_exports_module: false,
     has_writed_all_module: false,
+    has_writed_interop_require_default: false,
+    has_writed_interop_require_wildcard: false,
     contains_es_module_definition: false,
     contains_use_strict_declaration: false,
     class_default_number: 0,
-    function_default_number: 0
+    function_default_number: 0,
+    live_binding_mode: LiveBindingMode::Assignment,
+    strict_mode: true,
+    es_module_mode: EsModuleMode::Always,
+    add_module_exports: false,
+    merge_named_exports: false,
+    export_default_object_properties: false,
+    import_meta: DEFAULT_IMPORT_META_OPTIONS,
+    dynamic_import_mode: DynamicImportMode::Preserve,
+    esm_only_modules: Vec::new(),
+    top_level_await_mode: TopLevelAwaitMode::Error,
+    verify: false
+};
+
+const DEFAULT_IMPORT_META_OPTIONS: ImportMetaOptions = ImportMetaOptions {
+    url: true,
+    dirname: true,
+    filename: true,
+    resolve: true
 };
 
+/// node's convention for choosing what `require()` returns from an ES module:
+/// `export { value as "module.exports" }`.
+const MODULE_EXPORTS_EXPORT_NAME: &str = "module.exports";
+
 #[plugin_transform]
-pub fn module_exports_all(mut program: Program, _: TransformPluginProgramMetadata) -> Program {
-    program.visit_mut_with(&mut ModuleExportAll::from(DEFAULT_MODULE_EXPORT_ALL_STRUCT));
+pub fn module_exports_all(mut program: Program, metadata: TransformPluginProgramMetadata) -> Program {
+    let options = PluginOptions::from_config(metadata.get_transform_plugin_config());
+
+    let mut module_export_all = ModuleExportAll {
+        live_binding_mode: options.live_bindings,
+        strict_mode: options.strict_mode,
+        es_module_mode: options.es_module,
+        add_module_exports: options.add_module_exports,
+        merge_named_exports: options.merge_named_exports,
+        export_default_object_properties: options.export_default_object_properties,
+        import_meta: options.import_meta,
+        dynamic_import_mode: options.dynamic_import,
+        esm_only_modules: options.esm_only_modules,
+        top_level_await_mode: options.top_level_await,
+        verify: options.verify,
+        ..DEFAULT_MODULE_EXPORT_ALL_STRUCT
+    };
+
+    program.visit_mut_with(&mut module_export_all);
 
     program
 }
@@ -26,301 +74,1558 @@ pub fn module_exports_all(mut program: Program, _: TransformPluginProgramMetadat
 struct ModuleExportAll {
     has_writed_name_exports_module: bool,
     has_writed_all_module: bool,
+    has_writed_interop_require_default: bool,
+    has_writed_interop_require_wildcard: bool,
     contains_use_strict_declaration: bool,
     contains_es_module_definition: bool,
     class_default_number: i32,
     function_default_number: i32,
+    live_binding_mode: LiveBindingMode,
+    strict_mode: bool,
+    es_module_mode: EsModuleMode,
+    add_module_exports: bool,
+    merge_named_exports: bool,
+    export_default_object_properties: bool,
+    import_meta: ImportMetaOptions,
+    dynamic_import_mode: DynamicImportMode,
+    esm_only_modules: Vec<String>,
+    top_level_await_mode: TopLevelAwaitMode,
+    verify: bool,
 }
 
-impl VisitMut for ModuleExportAll {
-    fn visit_mut_module(&mut self, module: &mut Module) {
-        self.visit_mut_program_module(module);
-    }
+/// Options accepted in `.swcrc`, e.g.
+/// `["@konekti/swc-plugin-module-exports", { "liveBindings": "getter" }]`.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields, default)]
+struct PluginOptions {
+    live_bindings: LiveBindingMode,
+    strict_mode: bool,
+    es_module: EsModuleMode,
+    add_module_exports: bool,
+    merge_named_exports: bool,
+    export_default_object_properties: bool,
+    import_meta: ImportMetaOptions,
+    dynamic_import: DynamicImportMode,
+    esm_only_modules: Vec<String>,
+    top_level_await: TopLevelAwaitMode,
+    verify: bool
 }
 
-fn create_expression_statement_as_stmt(expr: Box<Expr>) -> Stmt {
-    Stmt::Expr(ExprStmt {
-        span: DUMMY_SP,
-        expr
-    })
+impl Default for PluginOptions {
+    fn default() -> Self {
+        PluginOptions {
+            live_bindings: LiveBindingMode::default(),
+            strict_mode: true,
+            es_module: EsModuleMode::default(),
+            add_module_exports: false,
+            merge_named_exports: false,
+            export_default_object_properties: false,
+            import_meta: DEFAULT_IMPORT_META_OPTIONS,
+            dynamic_import: DynamicImportMode::default(),
+            esm_only_modules: Vec::new(),
+            top_level_await: TopLevelAwaitMode::default(),
+            verify: false
+        }
+    }
 }
 
-fn create_js_string_as_box_expr(js_word: &str) -> Box<Expr> {
-    let symbol = "\"";
+impl PluginOptions {
+    fn from_config(config: Option<String>) -> PluginOptions {
+        let config = match config {
+            Some(config) if !config.trim().is_empty() => config,
+            _ => return PluginOptions::default()
+        };
+
+        serde_json::from_str(&config).unwrap_or_else(|error| {
+            match PluginOptions::find_invalid_key(&config) {
+                Some(key) => panic!("Invalid options for @konekti/swc-plugin-module-exports: {}: {}", key, error),
+                None => panic!("Invalid options for @konekti/swc-plugin-module-exports: {}", error)
+            }
+        })
+    }
 
-    Box::new(Expr::Lit(Lit::Str(Str {
-        span: DUMMY_SP,
-        value: JsWord::from(js_word),
-        raw: Some(Atom::new(format!("{}{}{}", symbol, js_word, symbol)))
-    })))
-}
+    // serde_json only names the key of an unknown field, so a value of the
+    // wrong type is traced back by deserializing every key on its own.
+    fn find_invalid_key(config: &str) -> Option<String> {
+        let options = serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(config).ok()?;
 
-fn create_js_object_as_box_expr(props: Vec<PropOrSpread>) -> Box<Expr> {
-    Box::new(Expr::Object(ObjectLit {
-        span: DUMMY_SP,
-        props
-    }))
-}
+        options.into_iter().find_map(|(key, value)| {
+            let option = serde_json::Value::Object(serde_json::Map::from_iter([(key.clone(), value)]));
 
-fn create_literal_prop(
-    prop_name: &str,
-    prop_name_optional: bool,
-    literal_value: Lit
-) -> PropOrSpread {
-    PropOrSpread::Prop(Box::new(Prop::KeyValue(KeyValueProp {
-        key: create_prop_name(
-            prop_name,
-            prop_name_optional
-        ),
-        value: create_literal_prop_value(literal_value)
-    })))
+            serde_json::from_value::<PluginOptions>(option).err().map(|_| key)
+        })
+    }
 }
 
-fn create_prop(
-    prop_name: &str,
-    prop_name_optional: bool,
-    value: Expr
-) -> PropOrSpread {
-    PropOrSpread::Prop(Box::new(Prop::KeyValue(KeyValueProp {
-        key: create_prop_name(
-            prop_name,
-            prop_name_optional
-        ),
-        value: Box::new(value)
-    })))
+/// Which `import.meta` properties are rewritten to their CommonJS equivalent.
+/// Disabled properties are left untouched.
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields, default)]
+struct ImportMetaOptions {
+    url: bool,
+    dirname: bool,
+    filename: bool,
+    resolve: bool
 }
 
-fn create_literal_prop_value(literal_value: Lit) -> Box<Expr> {
-    Box::new(Expr::Lit(literal_value))
+impl Default for ImportMetaOptions {
+    fn default() -> Self {
+        DEFAULT_IMPORT_META_OPTIONS
+    }
 }
 
-fn create_prop_name(prop_name: &str, optional: bool) -> PropName {
-    PropName::Ident(Ident {
-        span: DUMMY_SP,
-        sym: JsWord::from(prop_name),
-        optional
-    })
+/// How `import()` expressions are compiled.
+#[derive(Clone, Copy, PartialEq, Deserialize, Default)]
+#[serde(rename_all = "kebab-case")]
+enum DynamicImportMode {
+    /// `import()` is left as is; node loads CommonJS and ES modules with it.
+    #[default]
+    Preserve,
+    /// `Promise.resolve().then(() => _interop_require_wildcard(require(...)))`.
+    Require,
+    /// Like `Require`, except for specifiers listed in `esmOnlyModules`,
+    /// which can't be required and keep their `import()`.
+    ImportForEsmOnly
 }
 
-fn create_js_string_as_stmt(js_word: &str) -> Stmt {
-    let symbol = "\"";
-
-    create_expression_statement_as_stmt(Lit::into(Lit::Str(Str {
-        span: DUMMY_SP,
-        value: JsWord::from(js_word),
-        raw: Some(Atom::new(format!("{}{}{}", symbol, js_word, symbol)))
-    })))
+/// What happens to modules using top-level `await`, which CommonJS can't express.
+#[derive(Clone, Copy, PartialEq, Deserialize, Default)]
+#[serde(rename_all = "kebab-case")]
+enum TopLevelAwaitMode {
+    /// The build fails at the first top-level `await`.
+    #[default]
+    Error,
+    /// The body runs in an async function, and `exports.__tla` is the promise
+    /// that settles once the exports are populated.
+    Promise
 }
 
-fn create_call_expression(
-    callee: Callee,
-    args: Vec<ExprOrSpread>,
-    type_args: Option<Box<TsTypeParamInstantiation>>
-) -> Expr {
-    Expr::Call(CallExpr {
-        span: DUMMY_SP,
-        args,
-        callee,
-        type_args
-    })
+/// How exported bindings that are reassigned after their declaration are
+/// kept in sync with `exports`.
+#[derive(Clone, Copy, PartialEq, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+enum LiveBindingMode {
+    /// Every assignment also writes `exports.name`, as tsc does.
+    #[default]
+    Assignment,
+    /// `exports.name` is an enumerable getter over the local binding.
+    Getter
 }
 
-fn create_member_property(name: &str, optional: bool) -> MemberProp {
-    MemberProp::Ident(Ident {
-        span: DUMMY_SP,
-        optional,
-        sym: JsWord::from(name)
-    })
+/// When the `__esModule` interop flag is written to `exports`.
+#[derive(Clone, Copy, PartialEq, Deserialize, Default)]
+#[serde(rename_all = "kebab-case")]
+enum EsModuleMode {
+    /// `Object.defineProperty(exports, "__esModule", ...)` on every module.
+    #[default]
+    Always,
+    /// The flag is never written.
+    Never,
+    /// Only modules with a default export get the flag, so consumers don't
+    /// unwrap a `.default` that isn't there.
+    OnlyWhenDefaultExport,
+    /// Plain `exports.__esModule = true` assignment.
+    Loose
 }
 
-fn create_argument_identifier(name: &str, optional: bool) -> Box<Expr> {
-    Box::new(Expr::Ident(Ident {
-        span: DUMMY_SP,
-        sym: JsWord::from(name),
-        optional
-    }))
+impl VisitMut for ModuleExportAll {
+    fn visit_mut_module(&mut self, module: &mut Module) {
+        self.visit_mut_program_module(module);
+    }
 }
 
-fn create_argument_expr_or_spread(expr: Box<Expr>, is_spread: bool) -> ExprOrSpread {
-    let use_spread;
+#[derive(Default)]
+struct ModuleIdentifierCollector {
+    names: HashSet<JsWord>,
+    value_identifiers: HashSet<Id>
+}
 
-    if is_spread {
-        use_spread = Some(DUMMY_SP)
-    } else {
-        use_spread = None
+impl ModuleIdentifierCollector {
+    fn is_used_as_value(&self, ident: &Ident) -> bool {
+        self.value_identifiers.contains(&ident.to_id())
     }
 
-    ExprOrSpread {
-        spread: use_spread,
-        expr
+    fn create_unique_name(&mut self, module_path: &str) -> JsWord {
+        let base_name = format!("_{}", create_module_identifier_name(module_path));
+        let mut unique_name = base_name.clone();
+        let mut suffix = 0;
+
+        while self.names.contains(&JsWord::from(unique_name.as_str())) {
+            suffix += 1;
+            unique_name = format!("{}{}", base_name, suffix);
+        }
+
+        let unique_name = JsWord::from(unique_name);
+        self.names.insert(unique_name.clone());
+
+        unique_name
     }
 }
 
-fn create_callee_member_expression(
-    object: Expr,
-    property: &str
-) -> Callee {
-    Callee::Expr(Box::new(Expr::from(MemberExpr {
-        span: DUMMY_SP,
-        obj: Box::new(object),
-        prop: create_member_property(
-            property,
-            false
-        )
-    })))
-}
+impl Visit for ModuleIdentifierCollector {
+    fn visit_ident(&mut self, ident: &Ident) {
+        self.names.insert(ident.sym.clone());
+        self.value_identifiers.insert(ident.to_id());
+    }
 
-fn create_member_expression(
-    object: Expr,
-    property: &str
-) -> MemberExpr {
-    MemberExpr {
-        span: DUMMY_SP,
-        obj: Box::new(object),
-        prop: create_member_property(
-            property,
-            false
-        )
+    fn visit_import_decl(&mut self, import_declaration: &ImportDecl) {
+        for specifier in &import_declaration.specifiers {
+            self.names.insert(get_import_specifier_local(specifier).sym.clone());
+        }
     }
-}
 
-fn create_member_expression_by_key(
-    object: Expr,
-    property: MemberProp
-) -> MemberExpr {
-    MemberExpr {
-        span: DUMMY_SP,
-        obj: Box::new(object),
-        prop: property
+    fn visit_ts_import_equals_decl(&mut self, import_equals: &TsImportEqualsDecl) {
+        self.names.insert(import_equals.id.sym.clone());
+
+        import_equals.module_ref.visit_with(self);
     }
-}
 
-fn create_literal_boolean(bool_value: bool) -> Lit {
-    Lit::Bool(Bool {
-        span: DUMMY_SP,
-        value: bool_value
-    })
-}
+    fn visit_ts_type(&mut self, _: &TsType) {}
 
-fn create_literal_string(string_value: &str) -> Lit {
-    let symbol = "\"";
+    fn visit_ts_type_param_decl(&mut self, _: &TsTypeParamDecl) {}
 
-    Lit::Str(Str {
-        span: DUMMY_SP,
-        value: JsWord::from(string_value),
-        raw: Some(Atom::new(format!("{}{}{}", symbol, string_value, symbol)))
-    })
-}
+    fn visit_ts_type_param_instantiation(&mut self, _: &TsTypeParamInstantiation) {}
 
-fn create_identifier(identifier_name: &str) -> Ident {
-    Ident::new(
-        JsWord::from(identifier_name),
-        DUMMY_SP
-    )
-}
+    fn visit_ts_expr_with_type_args(&mut self, _: &TsExprWithTypeArgs) {}
 
-fn create_function(
-    params: Vec<Param>,
-    decorators: Vec<Decorator>,
-    body: Option<BlockStmt>,
-    is_generator: bool,
-    is_async: bool,
-    type_params: Option<Box<TsTypeParamDecl>>,
-    return_type: Option<Box<TsTypeAnn>>
-) -> Function {
-    Function {
-        params,
-        decorators,
-        span: DUMMY_SP,
-        body,
-        is_generator,
-        is_async,
-        type_params,
-        return_type
-    }
-}
+    fn visit_ts_interface_decl(&mut self, _: &TsInterfaceDecl) {}
 
-fn create_pat_ident(
-    id: Ident,
-    type_ann: Option<Box<TsTypeAnn>>
-) -> Pat {
-    Pat::Ident(BindingIdent {
-        id,
-        type_ann
-    })
+    fn visit_ts_type_alias_decl(&mut self, _: &TsTypeAliasDecl) {}
 }
 
-fn create_param(
-    param_name: &str
-) -> Param {
-    Param {
-        span: DUMMY_SP,
-        decorators: vec![],
-        pat: create_pat_ident(
-            create_identifier(param_name),
-            None
-        )
-    }
+#[derive(Default)]
+struct ImportedBindingRewriter {
+    imported_bindings: HashMap<Id, (Ident, JsWord)>
 }
 
-fn create_block_statement(statements: Vec<Stmt>) -> BlockStmt {
-    BlockStmt {
-        span: DUMMY_SP,
-        stmts: statements
+impl ImportedBindingRewriter {
+    fn create_imported_binding_expression(&self, ident: &Ident) -> Option<Expr> {
+        self.imported_bindings
+            .get(&ident.to_id())
+            .map(|(module_identifier, imported_name)| Expr::from(MemberExpr {
+                span: ident.span,
+                obj: Box::new(Expr::from(module_identifier.clone())),
+                prop: create_member_property(imported_name, false)
+            }))
     }
-}
 
-fn create_fn_declaration(
-    func_name: &str,
-    declare: bool,
-    function: Function
-) -> Stmt {
-    Stmt::Decl(Decl::Fn(FnDecl {
-        ident: create_identifier(func_name),
-        declare,
-        function: Box::new(function)
-    }))
+    fn create_unbound_imported_binding_expression(&self, expr: &Expr) -> Option<Box<Expr>> {
+        // (0, _module.name) so the call is not bound to the module object
+        match expr {
+            Expr::Ident(ident) => self.create_imported_binding_expression(ident).map(|binding_expression| {
+                Box::new(Expr::Paren(ParenExpr {
+                    span: DUMMY_SP,
+                    expr: Box::new(create_sequence_expression(vec![
+                        Expr::Lit(create_literal_number(0.0)),
+                        binding_expression
+                    ]))
+                }))
+            }),
+            _ => None
+        }
+    }
 }
 
-fn create_fn_expression(
-    func_name: Option<&str>,
-    function: Function
-) -> Expr {
-    Expr::Fn(FnExpr {
-        ident: match func_name {
-            Some(name) => Some(create_identifier(name)),
-            None => None
-        },
-        function: Box::new(function)
-    })
-}
+impl VisitMut for ImportedBindingRewriter {
+    fn visit_mut_expr(&mut self, expr: &mut Expr) {
+        if let Expr::Ident(ident) = expr {
+            if let Some(binding_expression) = self.create_imported_binding_expression(ident) {
+                *expr = binding_expression;
+                return;
+            }
+        }
 
-fn create_if_statement(
-    test: Expr,
-    cons: Stmt,
-    alt: Option<Box<Stmt>>
-) -> Stmt {
-    Stmt::If(IfStmt {
-        span: DUMMY_SP,
-        test: Box::new(test),
-        cons: Box::new(cons),
-        alt
-    })
-}
+        expr.visit_mut_children_with(self);
+    }
 
-fn create_return_statement(
-    arg: Option<Expr>
-) -> ReturnStmt {
-    ReturnStmt {
-        span: DUMMY_SP,
-        arg: match arg {
-            Some(some_arg) => Some(Box::new(some_arg)),
-            None => None
+    fn visit_mut_callee(&mut self, callee: &mut Callee) {
+        if let Callee::Expr(expr) = callee {
+            if let Some(unbound_expression) = self.create_unbound_imported_binding_expression(expr) {
+                *expr = unbound_expression;
+                return;
+            }
         }
+
+        callee.visit_mut_children_with(self);
     }
-}
 
-fn create_bin_expression(
+    fn visit_mut_opt_call(&mut self, opt_call: &mut OptCall) {
+        if let Some(unbound_expression) = self.create_unbound_imported_binding_expression(&opt_call.callee) {
+            opt_call.callee = unbound_expression;
+        }
+
+        opt_call.visit_mut_children_with(self);
+    }
+
+    fn visit_mut_tagged_tpl(&mut self, tagged_template: &mut TaggedTpl) {
+        if let Some(unbound_expression) = self.create_unbound_imported_binding_expression(&tagged_template.tag) {
+            tagged_template.tag = unbound_expression;
+        }
+
+        tagged_template.visit_mut_children_with(self);
+    }
+
+    fn visit_mut_prop(&mut self, prop: &mut Prop) {
+        if let Prop::Shorthand(ident) = prop {
+            if let Some(binding_expression) = self.create_imported_binding_expression(ident) {
+                *prop = Prop::KeyValue(KeyValueProp {
+                    key: create_prop_name(&ident.sym, false),
+                    value: Box::new(binding_expression)
+                });
+                return;
+            }
+        }
+
+        prop.visit_mut_children_with(self);
+    }
+
+    fn visit_mut_jsx_element_name(&mut self, element_name: &mut JSXElementName) {
+        if let JSXElementName::Ident(ident) = element_name {
+            if let Some((module_identifier, imported_name)) = self.imported_bindings.get(&ident.to_id()) {
+                *element_name = JSXElementName::JSXMemberExpr(JSXMemberExpr {
+                    obj: JSXObject::Ident(module_identifier.clone()),
+                    prop: create_identifier(imported_name)
+                });
+                return;
+            }
+        }
+
+        element_name.visit_mut_children_with(self);
+    }
+
+    fn visit_mut_jsx_object(&mut self, jsx_object: &mut JSXObject) {
+        if let JSXObject::Ident(ident) = jsx_object {
+            if let Some((module_identifier, imported_name)) = self.imported_bindings.get(&ident.to_id()) {
+                *jsx_object = JSXObject::JSXMemberExpr(Box::new(JSXMemberExpr {
+                    obj: JSXObject::Ident(module_identifier.clone()),
+                    prop: create_identifier(imported_name)
+                }));
+                return;
+            }
+        }
+
+        jsx_object.visit_mut_children_with(self);
+    }
+}
+
+#[derive(Default)]
+struct ReassignmentCollector {
+    reassigned_identifiers: HashSet<Id>
+}
+
+impl ReassignmentCollector {
+    fn insert_pat(&mut self, pat: &Pat) {
+        let mut identifiers = Vec::new();
+        collect_pat_identifiers(pat, &mut identifiers);
+
+        for ident in identifiers {
+            self.reassigned_identifiers.insert(ident.to_id());
+        }
+    }
+
+    fn insert_for_head(&mut self, for_head: &ForHead) {
+        match for_head {
+            ForHead::Pat(pat) => self.insert_pat(pat),
+            ForHead::VarDecl(var_declaration) if var_declaration.kind == VarDeclKind::Var => {
+                for declarator in &var_declaration.decls {
+                    self.insert_pat(&declarator.name);
+                }
+            },
+            _ => {}
+        }
+    }
+}
+
+impl Visit for ReassignmentCollector {
+    fn visit_assign_expr(&mut self, assign_expression: &AssignExpr) {
+        match &assign_expression.left {
+            PatOrExpr::Pat(pat) => self.insert_pat(pat),
+            PatOrExpr::Expr(expr) => {
+                if let Expr::Ident(ident) = &**expr {
+                    self.reassigned_identifiers.insert(ident.to_id());
+                }
+            }
+        }
+
+        assign_expression.visit_children_with(self);
+    }
+
+    fn visit_update_expr(&mut self, update_expression: &UpdateExpr) {
+        if let Expr::Ident(ident) = &*update_expression.arg {
+            self.reassigned_identifiers.insert(ident.to_id());
+        }
+
+        update_expression.visit_children_with(self);
+    }
+
+    fn visit_for_in_stmt(&mut self, for_in_statement: &ForInStmt) {
+        self.insert_for_head(&for_in_statement.left);
+
+        for_in_statement.visit_children_with(self);
+    }
+
+    fn visit_for_of_stmt(&mut self, for_of_statement: &ForOfStmt) {
+        self.insert_for_head(&for_of_statement.left);
+
+        for_of_statement.visit_children_with(self);
+    }
+}
+
+struct LiveBindingRewriter {
+    reassigned_identifiers: HashSet<Id>,
+    exported_bindings: HashMap<Id, Vec<JsWord>>,
+    temporary_identifier: Ident,
+    has_used_temporary: bool
+}
+
+impl LiveBindingRewriter {
+    fn get_assigned_export_identifiers(&self, left: &PatOrExpr) -> Vec<Ident> {
+        let mut identifiers = Vec::new();
+
+        match left {
+            PatOrExpr::Pat(pat) => collect_pat_identifiers(pat, &mut identifiers),
+            PatOrExpr::Expr(expr) => {
+                if let Expr::Ident(ident) = &**expr {
+                    identifiers.push(ident.clone());
+                }
+            }
+        }
+
+        identifiers
+            .into_iter()
+            .filter(|ident| self.exported_bindings.contains_key(&ident.to_id()))
+            .collect()
+    }
+
+    fn get_simple_assignment_target<'a>(&self, left: &'a PatOrExpr) -> Option<&'a Ident> {
+        match left {
+            PatOrExpr::Pat(pat) => pat.as_ident().map(|binding_ident| &binding_ident.id),
+            PatOrExpr::Expr(expr) => expr.as_ident()
+        }
+    }
+
+    fn create_export_updates(&self, identifiers: &[Ident]) -> Vec<Expr> {
+        let mut export_updates = Vec::new();
+
+        for ident in identifiers {
+            for export_name in &self.exported_bindings[&ident.to_id()] {
+                export_updates.push(create_assignment_expression(
+                    AssignOp::Assign,
+                    PatOrExpr::Expr(Box::new(Expr::from(create_member_expression(
+                        Expr::from(create_identifier("exports")),
+                        export_name
+                    )))),
+                    Box::new(Expr::from(ident.clone()))
+                ));
+            }
+        }
+
+        export_updates
+    }
+
+    fn wrap_with_export_assignments(&self, ident: &Ident, expr: Expr) -> Expr {
+        // exports.b = exports.a = <expr>
+        self.exported_bindings[&ident.to_id()]
+            .iter()
+            .fold(expr, |value, export_name| create_assignment_expression(
+                AssignOp::Assign,
+                PatOrExpr::Expr(Box::new(Expr::from(create_member_expression(
+                    Expr::from(create_identifier("exports")),
+                    export_name
+                )))),
+                Box::new(value)
+            ))
+    }
+
+    fn rewrite_reassignment(&mut self, expr: &Expr, is_value_used: bool) -> Option<Expr> {
+        match expr {
+            Expr::Assign(assign_expression) => {
+                let identifiers = self.get_assigned_export_identifiers(&assign_expression.left);
+
+                if identifiers.is_empty() {
+                    return None;
+                }
+
+                if let Some(ident) = self.get_simple_assignment_target(&assign_expression.left) {
+                    return Some(self.wrap_with_export_assignments(ident, expr.clone()));
+                }
+
+                let mut sequence = Vec::new();
+
+                if is_value_used {
+                    sequence.push(self.assign_temporary(expr.clone()));
+                } else {
+                    sequence.push(expr.clone());
+                }
+
+                sequence.append(&mut self.create_export_updates(&identifiers));
+
+                if is_value_used {
+                    sequence.push(Expr::from(self.temporary_identifier.clone()));
+                }
+
+                Some(create_sequence_expression(sequence))
+            },
+            Expr::Update(update_expression) => {
+                let ident = match &*update_expression.arg {
+                    Expr::Ident(ident) if self.exported_bindings.contains_key(&ident.to_id()) => ident,
+                    _ => return None
+                };
+
+                if update_expression.prefix || !is_value_used {
+                    return Some(self.wrap_with_export_assignments(ident, Expr::Update(UpdateExpr {
+                        prefix: true,
+                        ..update_expression.clone()
+                    })));
+                }
+
+                // (_tmp = count++, exports.count = count, _tmp)
+                let mut sequence = vec![self.assign_temporary(expr.clone())];
+                sequence.append(&mut self.create_export_updates(&[ident.clone()]));
+                sequence.push(Expr::from(self.temporary_identifier.clone()));
+
+                Some(create_sequence_expression(sequence))
+            },
+            _ => None
+        }
+    }
+
+    fn assign_temporary(&mut self, expr: Expr) -> Expr {
+        self.has_used_temporary = true;
+
+        create_assignment_expression(
+            AssignOp::Assign,
+            PatOrExpr::Pat(Box::new(create_pat_ident(self.temporary_identifier.clone(), None))),
+            Box::new(expr)
+        )
+    }
+
+    fn prepend_for_head_export_updates(&self, left: &ForHead, body: &mut Box<Stmt>) {
+        let mut identifiers = Vec::new();
+
+        match left {
+            ForHead::Pat(pat) => collect_pat_identifiers(pat, &mut identifiers),
+            ForHead::VarDecl(var_declaration) if var_declaration.kind == VarDeclKind::Var => {
+                for declarator in &var_declaration.decls {
+                    collect_pat_identifiers(&declarator.name, &mut identifiers);
+                }
+            },
+            _ => {}
+        }
+
+        identifiers.retain(|ident| self.exported_bindings.contains_key(&ident.to_id()));
+
+        if identifiers.is_empty() {
+            return;
+        }
+
+        let mut statements: Vec<Stmt> = self.create_export_updates(&identifiers)
+            .into_iter()
+            .map(|export_update| create_expression_statement_as_stmt(Box::new(export_update)))
+            .collect();
+
+        match &mut **body {
+            Stmt::Block(block_statement) => {
+                statements.append(&mut block_statement.stmts);
+                block_statement.stmts = statements;
+            },
+            other_statement => {
+                statements.push(other_statement.clone());
+                *body = Box::new(Stmt::from(create_block_statement(statements)));
+            }
+        }
+    }
+}
+
+impl VisitMut for LiveBindingRewriter {
+    fn visit_mut_expr(&mut self, expr: &mut Expr) {
+        expr.visit_mut_children_with(self);
+
+        if let Some(rewritten_expression) = self.rewrite_reassignment(expr, true) {
+            *expr = rewritten_expression;
+        }
+    }
+
+    fn visit_mut_expr_stmt(&mut self, expression_statement: &mut ExprStmt) {
+        expression_statement.expr.visit_mut_children_with(self);
+
+        if let Some(rewritten_expression) = self.rewrite_reassignment(&expression_statement.expr, false) {
+            *expression_statement.expr = rewritten_expression;
+        }
+    }
+
+    fn visit_mut_for_in_stmt(&mut self, for_in_statement: &mut ForInStmt) {
+        for_in_statement.visit_mut_children_with(self);
+
+        self.prepend_for_head_export_updates(&for_in_statement.left, &mut for_in_statement.body);
+    }
+
+    fn visit_mut_for_of_stmt(&mut self, for_of_statement: &mut ForOfStmt) {
+        for_of_statement.visit_mut_children_with(self);
+
+        self.prepend_for_head_export_updates(&for_of_statement.left, &mut for_of_statement.body);
+    }
+}
+
+#[derive(Default)]
+struct ModuleHeader {
+    preamble: Vec<ModuleItem>,
+    pre_declarations: Vec<JsWord>,
+    hoisted_exports: Vec<ModuleItem>,
+    helpers: Vec<ModuleItem>
+}
+
+impl ModuleHeader {
+    // Same layout as tsc: function exports are assigned before any other
+    // code runs, so a circular require already sees them.
+    fn into_module_items(self) -> Vec<ModuleItem> {
+        let mut module_items = self.preamble;
+        let mut written_pre_declarations = HashSet::new();
+
+        for export_name in self.pre_declarations {
+            if written_pre_declarations.insert(export_name.clone()) {
+                module_items.push(ModuleItem::from(define_export_pre_declaration(&export_name)));
+            }
+        }
+
+        module_items.extend(self.hoisted_exports);
+        module_items.extend(self.helpers);
+
+        module_items
+    }
+}
+
+#[derive(Default)]
+struct ExpectedExports {
+    export_names: Vec<(JsWord, Span)>,
+    reexport_sources: Vec<(JsWord, Span)>
+}
+
+struct ImportMetaRewriter {
+    options: ImportMetaOptions
+}
+
+impl ImportMetaRewriter {
+    fn create_import_meta_replacement(&self, property_name: &str) -> Option<Option<Expr>> {
+        let replacement = match property_name {
+            "url" if self.options.url => Some(Expr::from(create_member_expression(
+                create_call_expression(
+                    Callee::Expr(Box::new(Expr::from(create_member_expression(
+                        create_require_call_expression("url"),
+                        "pathToFileURL"
+                    )))),
+                    vec![
+                    create_argument_expr_or_spread(create_argument_identifier("__filename", false), false)
+                    ],
+                    None
+                ),
+                "href"
+            ))),
+            "dirname" if self.options.dirname => Some(Expr::from(create_identifier("__dirname"))),
+            "filename" if self.options.filename => Some(Expr::from(create_identifier("__filename"))),
+            "resolve" if self.options.resolve => Some(Expr::from(create_member_expression(
+                Expr::from(create_identifier("require")),
+                "resolve"
+            ))),
+            "url" | "dirname" | "filename" | "resolve" => None,
+            _ => return None
+        };
+
+        Some(replacement)
+    }
+}
+
+impl VisitMut for ImportMetaRewriter {
+    fn visit_mut_expr(&mut self, expr: &mut Expr) {
+        if let Expr::Member(MemberExpr { span, obj, prop: MemberProp::Ident(property) }) = expr {
+            if let Expr::MetaProp(MetaPropExpr { kind: MetaPropKind::ImportMeta, .. }) = &**obj {
+                match self.create_import_meta_replacement(&property.sym) {
+                    Some(Some(replacement)) => *expr = replacement,
+                    Some(None) => {},
+                    None => HANDLER.with(|handler| {
+                        handler
+                            .struct_span_err(
+                                *span,
+                                &format!("import.meta.{} has no CommonJS equivalent", property.sym)
+                            )
+                            .emit()
+                    })
+                }
+
+                return;
+            }
+        }
+
+        if let Expr::MetaProp(MetaPropExpr { span, kind: MetaPropKind::ImportMeta }) = expr {
+            HANDLER.with(|handler| {
+                handler
+                    .struct_span_err(*span, "import.meta can only be compiled to CommonJS through one of its properties")
+                    .emit()
+            });
+
+            return;
+        }
+
+        expr.visit_mut_children_with(self);
+    }
+}
+
+struct DynamicImportRewriter<'a> {
+    mode: DynamicImportMode,
+    esm_only_modules: &'a [String],
+    has_used_interop_helper: bool
+}
+
+impl DynamicImportRewriter<'_> {
+    fn is_esm_only_module(&self, module_path: &str) -> bool {
+        self.esm_only_modules.iter().any(|esm_only_module| {
+            module_path == esm_only_module || module_path.starts_with(&format!("{}/", esm_only_module))
+        })
+    }
+
+    fn create_interop_require_expression(&mut self, module_path: Expr) -> Expr {
+        self.has_used_interop_helper = true;
+
+        create_call_expression(
+            Callee::Expr(Box::new(Expr::from(create_identifier("_interop_require_wildcard")))),
+            vec![
+            create_argument_expr_or_spread(
+                Box::new(create_call_expression(
+                    Callee::Expr(Box::new(Expr::from(create_identifier("require")))),
+                    vec![
+                    create_argument_expr_or_spread(Box::new(module_path), false)
+                    ],
+                    None
+                )),
+                false
+            )
+            ],
+            None
+        )
+    }
+
+    fn rewrite_dynamic_import(&mut self, module_path: &Expr) -> Option<Expr> {
+        let literal_module_path = match module_path {
+            Expr::Lit(Lit::Str(str)) => Some(str.value.to_string()),
+            _ => None
+        };
+
+        let keeps_import = match self.mode {
+            DynamicImportMode::Preserve => true,
+            DynamicImportMode::Require => false,
+            // A computed specifier can't be matched, so it keeps its import() too.
+            DynamicImportMode::ImportForEsmOnly => literal_module_path
+                .as_ref()
+                .map_or(true, |literal_module_path| self.is_esm_only_module(literal_module_path))
+        };
+
+        if keeps_import {
+            return None;
+        }
+
+        // A computed specifier is evaluated right away, as import() would do.
+        let (resolved_value, module_path, params) = match literal_module_path {
+            Some(_) => (vec![], module_path.clone(), vec![]),
+            None => {
+                let specifier = create_identifier("specifier");
+
+                (
+                    vec![create_argument_expr_or_spread(Box::new(module_path.clone()), false)],
+                    Expr::from(specifier.clone()),
+                    vec![create_pat_ident(specifier, None)]
+                )
+            }
+        };
+
+        let resolved_promise = create_call_expression(
+            create_callee_member_expression(Expr::from(create_identifier("Promise")), "resolve"),
+            resolved_value,
+            None
+        );
+        let interop_require_expression = self.create_interop_require_expression(module_path);
+
+        Some(create_call_expression(
+            Callee::Expr(Box::new(Expr::from(create_member_expression(resolved_promise, "then")))),
+            vec![
+            create_argument_expr_or_spread(
+                Box::new(create_arrow_expression(params, BlockStmtOrExpr::Expr(Box::new(interop_require_expression)), false)),
+                false
+            )
+            ],
+            None
+        ))
+    }
+}
+
+impl VisitMut for DynamicImportRewriter<'_> {
+    fn visit_mut_expr(&mut self, expr: &mut Expr) {
+        expr.visit_mut_children_with(self);
+
+        if let Expr::Call(CallExpr { callee: Callee::Import(_), args, .. }) = expr {
+            let rewritten_expression = match args.first() {
+                Some(ExprOrSpread { spread: None, expr: module_path }) => self.rewrite_dynamic_import(module_path),
+                _ => None
+            };
+
+            if let Some(rewritten_expression) = rewritten_expression {
+                *expr = rewritten_expression;
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct TopLevelAwaitFinder {
+    span: Option<Span>
+}
+
+impl Visit for TopLevelAwaitFinder {
+    fn visit_await_expr(&mut self, await_expression: &AwaitExpr) {
+        if self.span.is_none() {
+            self.span = Some(await_expression.span);
+        }
+    }
+
+    fn visit_for_of_stmt(&mut self, for_of_statement: &ForOfStmt) {
+        if for_of_statement.is_await && self.span.is_none() {
+            self.span = Some(for_of_statement.span);
+        }
+
+        for_of_statement.visit_children_with(self);
+    }
+
+    // Awaits inside functions belong to them, not to the module.
+    fn visit_function(&mut self, _: &Function) {}
+    fn visit_arrow_expr(&mut self, _: &ArrowExpr) {}
+}
+
+fn create_module_identifier_name(module_path: &str) -> String {
+    let file_name = module_path.rsplit('/').next().unwrap_or(module_path);
+    let file_name = match file_name.rfind('.') {
+        Some(extension_index) if extension_index > 0 => &file_name[..extension_index],
+        _ => file_name
+    };
+
+    let mut identifier_name = String::new();
+    let mut uppercase_next = false;
+
+    for character in file_name.chars() {
+        if character.is_ascii_alphanumeric() || character == '_' || character == '$' {
+            if uppercase_next && !identifier_name.is_empty() {
+                identifier_name.push(character.to_ascii_uppercase());
+            } else {
+                identifier_name.push(character);
+            }
+
+            uppercase_next = false;
+        } else {
+            uppercase_next = true;
+        }
+    }
+
+    if identifier_name.is_empty() {
+        return String::from("module");
+    }
+
+    identifier_name
+}
+
+fn collect_pat_identifiers(pat: &Pat, identifiers: &mut Vec<Ident>) {
+    match pat {
+        Pat::Ident(binding_ident) => identifiers.push(binding_ident.id.clone()),
+        Pat::Array(array_pat) => {
+            for element in array_pat.elems.iter().flatten() {
+                collect_pat_identifiers(element, identifiers);
+            }
+        },
+        Pat::Object(object_pat) => {
+            for prop in &object_pat.props {
+                match prop {
+                    ObjectPatProp::KeyValue(key_value_prop) => collect_pat_identifiers(&key_value_prop.value, identifiers),
+                    ObjectPatProp::Assign(assign_prop) => identifiers.push(assign_prop.key.clone()),
+                    ObjectPatProp::Rest(rest_pat) => collect_pat_identifiers(&rest_pat.arg, identifiers)
+                }
+            }
+        },
+        Pat::Rest(rest_pat) => collect_pat_identifiers(&rest_pat.arg, identifiers),
+        Pat::Assign(assign_pat) => collect_pat_identifiers(&assign_pat.left, identifiers),
+        Pat::Invalid(_) | Pat::Expr(_) => {}
+    }
+}
+
+fn get_declaration_value_identifiers(declaration: &Decl) -> Vec<Ident> {
+    let mut identifiers = Vec::new();
+
+    match declaration {
+        Decl::Class(class_declaration) if !class_declaration.declare => identifiers.push(class_declaration.ident.clone()),
+        // Overload signatures have no body and share the implementation's name.
+        Decl::Fn(function_declaration) if !function_declaration.declare && function_declaration.function.body.is_some() => {
+            identifiers.push(function_declaration.ident.clone())
+        },
+        Decl::Var(var_declaration) if !var_declaration.declare => {
+            for declarator in &var_declaration.decls {
+                collect_pat_identifiers(&declarator.name, &mut identifiers);
+            }
+        },
+        Decl::TsEnum(enum_declaration) if !enum_declaration.declare => identifiers.push(enum_declaration.id.clone()),
+        Decl::TsModule(module_declaration) if !module_declaration.declare => {
+            let is_instantiated = module_declaration.body.as_ref().map_or(false, is_instantiated_namespace_body);
+
+            if let (TsModuleName::Ident(ident), true) = (&module_declaration.id, is_instantiated) {
+                identifiers.push(ident.clone());
+            }
+        },
+        _ => {}
+    }
+
+    identifiers
+}
+
+// Namespaces holding only types are erased by tsc, so there is no value to export.
+fn is_instantiated_namespace_body(namespace_body: &TsNamespaceBody) -> bool {
+    match namespace_body {
+        TsNamespaceBody::TsModuleBlock(module_block) => module_block.body.iter().any(|node| match node {
+            ModuleItem::Stmt(Stmt::Decl(declaration)) => !get_declaration_value_identifiers(declaration).is_empty(),
+            ModuleItem::ModuleDecl(ModuleDecl::ExportDecl(export_declaration)) => {
+                !get_declaration_value_identifiers(&export_declaration.decl).is_empty()
+            },
+            ModuleItem::ModuleDecl(ModuleDecl::TsImportEquals(import_equals)) => !import_equals.is_type_only,
+            ModuleItem::Stmt(Stmt::Empty(_)) => false,
+            ModuleItem::Stmt(_) => true,
+            ModuleItem::ModuleDecl(_) => false
+        }),
+        TsNamespaceBody::TsNamespaceDecl(namespace_declaration) => {
+            !namespace_declaration.declare && is_instantiated_namespace_body(&namespace_declaration.body)
+        }
+    }
+}
+
+fn get_declared_value_identifiers(node: &ModuleItem) -> Vec<Ident> {
+    match node {
+        ModuleItem::Stmt(Stmt::Decl(declaration)) => get_declaration_value_identifiers(declaration),
+        ModuleItem::ModuleDecl(ModuleDecl::ExportDecl(export_declaration)) => get_declaration_value_identifiers(&export_declaration.decl),
+        ModuleItem::ModuleDecl(ModuleDecl::ExportDefaultDecl(export_default_declaration)) => match &export_default_declaration.decl {
+            DefaultDecl::Class(class_expression) => class_expression.ident.clone().into_iter().collect(),
+            DefaultDecl::Fn(function_expression) if function_expression.function.body.is_some() => {
+                function_expression.ident.clone().into_iter().collect()
+            },
+            _ => vec![]
+        },
+        ModuleItem::ModuleDecl(ModuleDecl::Import(import_declaration)) => import_declaration.specifiers
+            .iter()
+            .filter_map(|specifier| specifier.as_namespace().map(|namespace_specifier| namespace_specifier.local.clone()))
+            .collect(),
+        ModuleItem::ModuleDecl(ModuleDecl::TsImportEquals(import_equals)) if !import_equals.is_type_only => vec![import_equals.id.clone()],
+        _ => vec![]
+    }
+}
+
+fn has_value_export(node: &ModuleItem) -> bool {
+    match node {
+        ModuleItem::ModuleDecl(ModuleDecl::ExportDecl(export_declaration)) => {
+            !get_declaration_value_identifiers(&export_declaration.decl).is_empty()
+        },
+        ModuleItem::ModuleDecl(ModuleDecl::ExportNamed(named_export)) => !named_export.type_only && named_export.specifiers
+            .iter()
+            .any(|specifier| !matches!(specifier, ExportSpecifier::Named(named_specifier) if named_specifier.is_type_only)),
+        ModuleItem::ModuleDecl(ModuleDecl::ExportAll(export_all)) => !export_all.type_only,
+        ModuleItem::ModuleDecl(ModuleDecl::TsImportEquals(import_equals)) => import_equals.is_export && !import_equals.is_type_only,
+        ModuleItem::ModuleDecl(ModuleDecl::ExportDefaultDecl(_) | ModuleDecl::ExportDefaultExpr(_)) => is_default_export(node),
+        _ => false
+    }
+}
+
+// The named exports and `export *` sources an ES module importer should see,
+// following the same rules the transform uses to skip types. `default` and
+// `module.exports` are left out since node always provides them.
+fn get_expected_export_names(
+    body: &[ModuleItem],
+    top_level_declarations: &HashSet<Id>,
+    top_level_imports: &HashSet<Id>,
+    exposes_default_object_properties: bool
+) -> ExpectedExports {
+    let mut export_names = Vec::new();
+    let mut reexport_sources = Vec::new();
+
+    for node in body {
+        let module_declaration = match node {
+            ModuleItem::ModuleDecl(module_declaration) => module_declaration,
+            ModuleItem::Stmt(_) => continue
+        };
+
+        match module_declaration {
+            ModuleDecl::ExportDecl(export_declaration) => {
+                for ident in get_declaration_value_identifiers(&export_declaration.decl) {
+                    export_names.push((ident.sym.clone(), ident.span));
+                }
+            },
+            ModuleDecl::ExportNamed(named_export) if !named_export.type_only => {
+                for specifier in &named_export.specifiers {
+                    match specifier {
+                        ExportSpecifier::Named(named_specifier) if !named_specifier.is_type_only => {
+                            let is_value = match (&named_export.src, &named_specifier.orig) {
+                                (Some(_), _) => true,
+                                (None, ModuleExportName::Ident(local)) => {
+                                    top_level_declarations.contains(&local.to_id()) || top_level_imports.contains(&local.to_id())
+                                },
+                                (None, ModuleExportName::Str(_)) => false
+                            };
+
+                            if is_value {
+                                let exported = named_specifier.exported.as_ref().unwrap_or(&named_specifier.orig);
+
+                                export_names.push((get_module_export_name(exported), exported.span()));
+                            }
+                        },
+                        ExportSpecifier::Namespace(namespace_specifier) => {
+                            export_names.push((get_module_export_name(&namespace_specifier.name), namespace_specifier.span));
+                        },
+                        _ => {}
+                    }
+                }
+            },
+            // Only JSON modules take attributes, and they have nothing to re-export.
+            ModuleDecl::ExportAll(export_all) if !export_all.type_only && export_all.with.is_none() => {
+                reexport_sources.push((export_all.src.value.clone(), export_all.span));
+            },
+            ModuleDecl::TsImportEquals(import_equals) if import_equals.is_export && !import_equals.is_type_only => {
+                export_names.push((import_equals.id.sym.clone(), import_equals.id.span));
+            },
+            ModuleDecl::ExportDefaultExpr(export_default) if exposes_default_object_properties => {
+                if let Expr::Object(default_object) = export_default.expr.unwrap_parens() {
+                    for property_name in get_object_literal_property_names(default_object) {
+                        export_names.push((property_name, default_object.span));
+                    }
+                }
+            },
+            _ => {}
+        }
+    }
+
+    export_names.retain(|(export_name, _)| &**export_name != "default" && &**export_name != MODULE_EXPORTS_EXPORT_NAME);
+
+    ExpectedExports { export_names, reexport_sources }
+}
+
+// JSON modules only have a default export and no `__esModule` flag, so they
+// are required without interop. Other attributes can't be honored by require().
+fn is_json_module(import_attributes: Option<&ObjectLit>) -> bool {
+    let import_attributes = match import_attributes {
+        Some(import_attributes) => import_attributes,
+        None => return false
+    };
+
+    let mut is_json_module = false;
+
+    for attribute in &import_attributes.props {
+        let (key, value) = match attribute {
+            PropOrSpread::Prop(prop) => match &**prop {
+                Prop::KeyValue(KeyValueProp { key: PropName::Ident(key), value }) => (key.sym.clone(), value),
+                Prop::KeyValue(KeyValueProp { key: PropName::Str(key), value }) => (key.value.clone(), value),
+                _ => continue
+            },
+            PropOrSpread::Spread(_) => continue
+        };
+
+        match (&*key, &**value) {
+            ("type", Expr::Lit(Lit::Str(attribute_type))) if &*attribute_type.value == "json" => is_json_module = true,
+            ("type", attribute_type) => HANDLER.with(|handler| {
+                handler
+                    .struct_span_err(attribute_type.span(), "Only the \"json\" import attribute type can be compiled to CommonJS")
+                    .emit()
+            }),
+            (_, attribute_value) => HANDLER.with(|handler| {
+                handler
+                    .struct_span_err(attribute_value.span(), &format!("Unsupported import attribute \"{}\"", key))
+                    .emit()
+            })
+        }
+    }
+
+    is_json_module
+}
+
+fn emit_json_named_import_error(span: Span) {
+    HANDLER.with(|handler| {
+        handler
+            .struct_span_err(span, "JSON modules only have a default export")
+            .emit()
+    });
+}
+
+fn get_import_specifier_local(specifier: &ImportSpecifier) -> &Ident {
+    match specifier {
+        ImportSpecifier::Named(named_specifier) => &named_specifier.local,
+        ImportSpecifier::Default(default_specifier) => &default_specifier.local,
+        ImportSpecifier::Namespace(namespace_specifier) => &namespace_specifier.local
+    }
+}
+
+fn get_module_export_name(export_name: &ModuleExportName) -> JsWord {
+    match export_name {
+        ModuleExportName::Ident(ident) => ident.sym.clone(),
+        ModuleExportName::Str(str) => str.value.clone()
+    }
+}
+
+fn is_default_export(node: &ModuleItem) -> bool {
+    match node {
+        ModuleItem::ModuleDecl(ModuleDecl::ExportDefaultDecl(export_default)) => !export_default.decl.is_ts_interface_decl(),
+        ModuleItem::ModuleDecl(ModuleDecl::ExportDefaultExpr(_)) => true,
+        ModuleItem::ModuleDecl(ModuleDecl::ExportNamed(named_export)) if !named_export.type_only => {
+            named_export.specifiers.iter().any(|specifier| match specifier {
+                ExportSpecifier::Named(named_specifier) if !named_specifier.is_type_only => {
+                    &*get_module_export_name(named_specifier.exported.as_ref().unwrap_or(&named_specifier.orig)) == "default"
+                },
+                ExportSpecifier::Namespace(namespace_specifier) => &*get_module_export_name(&namespace_specifier.name) == "default",
+                _ => false
+            })
+        },
+        _ => false
+    }
+}
+
+fn has_only_default_export(body: &[ModuleItem]) -> bool {
+    let mut has_default_export = false;
+
+    for node in body {
+        let module_declaration = match node {
+            ModuleItem::ModuleDecl(module_declaration) => module_declaration,
+            ModuleItem::Stmt(_) => continue
+        };
+
+        match module_declaration {
+            ModuleDecl::Import(_) => {},
+            ModuleDecl::ExportDecl(export_declaration) => {
+                if !get_declaration_value_identifiers(&export_declaration.decl).is_empty() {
+                    return false;
+                }
+            },
+            ModuleDecl::ExportNamed(named_export) if named_export.type_only => {},
+            ModuleDecl::ExportNamed(named_export) => {
+                for specifier in &named_export.specifiers {
+                    let export_name = match specifier {
+                        ExportSpecifier::Named(named_specifier) if named_specifier.is_type_only => continue,
+                        ExportSpecifier::Named(named_specifier) => {
+                            get_module_export_name(named_specifier.exported.as_ref().unwrap_or(&named_specifier.orig))
+                        },
+                        ExportSpecifier::Namespace(namespace_specifier) => get_module_export_name(&namespace_specifier.name),
+                        ExportSpecifier::Default(default_specifier) => default_specifier.exported.sym.clone()
+                    };
+
+                    if &*export_name != "default" {
+                        return false;
+                    }
+
+                    has_default_export = true;
+                }
+            },
+            ModuleDecl::ExportAll(export_all) if export_all.type_only => {},
+            ModuleDecl::ExportDefaultDecl(_) | ModuleDecl::ExportDefaultExpr(_) => has_default_export |= is_default_export(node),
+            _ => return false
+        }
+    }
+
+    has_default_export
+}
+
+fn get_default_export_assignment_value(node: &ModuleItem) -> Option<&Expr> {
+    let assignment = match node {
+        ModuleItem::Stmt(Stmt::Expr(ExprStmt { expr, .. })) => expr.as_assign()?,
+        _ => return None
+    };
+
+    let target = match &assignment.left {
+        PatOrExpr::Expr(target) => target.as_member()?,
+        PatOrExpr::Pat(target) => target.as_expr()?.as_member()?
+    };
+
+    let is_exports_object = target.obj.as_ident().map_or(false, |ident| &*ident.sym == "exports");
+    let is_default_property = target.prop.as_ident().map_or(false, |ident| &*ident.sym == "default");
+
+    if assignment.op == AssignOp::Assign && is_exports_object && is_default_property {
+        Some(&assignment.right)
+    } else {
+        None
+    }
+}
+
+fn is_primitive_expression(expr: &Expr) -> bool {
+    match expr {
+        Expr::Lit(literal) => !matches!(literal, Lit::Regex(_) | Lit::JSXText(_)),
+        Expr::Tpl(_) | Expr::Unary(_) | Expr::Update(_) => true,
+        Expr::Bin(binary_expression) => !matches!(
+            binary_expression.op,
+            BinaryOp::LogicalOr | BinaryOp::LogicalAnd | BinaryOp::NullishCoalescing
+        ),
+        Expr::Paren(paren_expression) => is_primitive_expression(&paren_expression.expr),
+        _ => false
+    }
+}
+
+fn needs_export_pre_declaration(export_name: &str) -> bool {
+    export_name != "default" && export_name != MODULE_EXPORTS_EXPORT_NAME
+}
+
+// Non-ASCII names are kept as string keys, which every consumer reads the same way.
+fn is_identifier_name(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    name.is_ascii() && chars.next().map_or(false, Ident::is_valid_start) && chars.all(Ident::is_valid_continue)
+}
+
+fn get_object_literal_property_names(object: &ObjectLit) -> Vec<JsWord> {
+    let mut property_names = Vec::new();
+
+    for property in &object.props {
+        let property_name = match property {
+            PropOrSpread::Prop(prop) => match &**prop {
+                Prop::Shorthand(ident) => Some(ident.sym.clone()),
+                Prop::KeyValue(KeyValueProp { key, .. }) | Prop::Method(MethodProp { key, .. }) => match key {
+                    PropName::Ident(ident) => Some(ident.sym.clone()),
+                    PropName::Str(str) => Some(str.value.clone()),
+                    _ => None
+                },
+                // Accessors would be read once and lose their liveness.
+                _ => None
+            },
+            PropOrSpread::Spread(_) => None
+        };
+
+        if let Some(property_name) = property_name {
+            if &*property_name != "default" && &*property_name != "__esModule" && !property_names.contains(&property_name) {
+                property_names.push(property_name);
+            }
+        }
+    }
+
+    property_names
+}
+
+fn get_directive_value(node: &ModuleItem) -> Option<&JsWord> {
+    match node {
+        ModuleItem::Stmt(Stmt::Expr(ExprStmt { expr, .. })) => match &**expr {
+            Expr::Lit(Lit::Str(directive)) => Some(&directive.value),
+            _ => None
+        },
+        _ => None
+    }
+}
+
+fn create_expression_statement_as_stmt(expr: Box<Expr>) -> Stmt {
+    Stmt::Expr(ExprStmt {
+        span: DUMMY_SP,
+        expr
+    })
+}
+
+fn create_string_raw(value: &str) -> Option<Atom> {
+    let symbol = "\"";
+
+    // Without a raw value the code generator escapes the string itself.
+    if value.chars().any(|char| char == '"' || char == '\\' || char.is_control() || !char.is_ascii()) {
+        return None;
+    }
+
+    Some(Atom::new(format!("{}{}{}", symbol, value, symbol)))
+}
+
+fn create_js_string_as_box_expr(js_word: &str) -> Box<Expr> {
+    Box::new(Expr::Lit(create_literal_string(js_word)))
+}
+
+fn create_js_object_as_box_expr(props: Vec<PropOrSpread>) -> Box<Expr> {
+    Box::new(Expr::Object(ObjectLit {
+        span: DUMMY_SP,
+        props
+    }))
+}
+
+fn create_literal_prop(
+    prop_name: &str,
+    prop_name_optional: bool,
+    literal_value: Lit
+) -> PropOrSpread {
+    PropOrSpread::Prop(Box::new(Prop::KeyValue(KeyValueProp {
+        key: create_prop_name(
+            prop_name,
+            prop_name_optional
+        ),
+        value: create_literal_prop_value(literal_value)
+    })))
+}
+
+fn create_prop(
+    prop_name: &str,
+    prop_name_optional: bool,
+    value: Expr
+) -> PropOrSpread {
+    PropOrSpread::Prop(Box::new(Prop::KeyValue(KeyValueProp {
+        key: create_prop_name(
+            prop_name,
+            prop_name_optional
+        ),
+        value: Box::new(value)
+    })))
+}
+
+fn create_literal_prop_value(literal_value: Lit) -> Box<Expr> {
+    Box::new(Expr::Lit(literal_value))
+}
+
+fn create_prop_name(prop_name: &str, optional: bool) -> PropName {
+    if !is_identifier_name(prop_name) {
+        return PropName::Str(Str {
+            span: DUMMY_SP,
+            value: JsWord::from(prop_name),
+            raw: create_string_raw(prop_name)
+        });
+    }
+
+    PropName::Ident(Ident {
+        span: DUMMY_SP,
+        sym: JsWord::from(prop_name),
+        optional
+    })
+}
+
+fn create_js_string_as_stmt(js_word: &str) -> Stmt {
+    let symbol = "\"";
+
+    create_expression_statement_as_stmt(Lit::into(Lit::Str(Str {
+        span: DUMMY_SP,
+        value: JsWord::from(js_word),
+        raw: Some(Atom::new(format!("{}{}{}", symbol, js_word, symbol)))
+    })))
+}
+
+fn create_call_expression(
+    callee: Callee,
+    args: Vec<ExprOrSpread>,
+    type_args: Option<Box<TsTypeParamInstantiation>>
+) -> Expr {
+    Expr::Call(CallExpr {
+        span: DUMMY_SP,
+        args,
+        callee,
+        type_args
+    })
+}
+
+fn create_member_property(name: &str, optional: bool) -> MemberProp {
+    // exports["my-name"] is still detected by cjs-module-lexer.
+    if !is_identifier_name(name) {
+        return MemberProp::Computed(ComputedPropName {
+            span: DUMMY_SP,
+            expr: create_js_string_as_box_expr(name)
+        });
+    }
+
+    MemberProp::Ident(Ident {
+        span: DUMMY_SP,
+        optional,
+        sym: JsWord::from(name)
+    })
+}
+
+fn create_argument_identifier(name: &str, optional: bool) -> Box<Expr> {
+    Box::new(Expr::Ident(Ident {
+        span: DUMMY_SP,
+        sym: JsWord::from(name),
+        optional
+    }))
+}
+
+fn create_argument_expr_or_spread(expr: Box<Expr>, is_spread: bool) -> ExprOrSpread {
+    let use_spread = if is_spread {
+        Some(DUMMY_SP)
+    } else {
+        None
+    };
+
+    ExprOrSpread {
+        spread: use_spread,
+        expr
+    }
+}
+
+fn create_callee_member_expression(
+    object: Expr,
+    property: &str
+) -> Callee {
+    Callee::Expr(Box::new(Expr::from(MemberExpr {
+        span: DUMMY_SP,
+        obj: Box::new(object),
+        prop: create_member_property(
+            property,
+            false
+        )
+    })))
+}
+
+fn create_member_expression(
+    object: Expr,
+    property: &str
+) -> MemberExpr {
+    MemberExpr {
+        span: DUMMY_SP,
+        obj: Box::new(object),
+        prop: create_member_property(
+            property,
+            false
+        )
+    }
+}
+
+fn create_member_expression_by_key(
+    object: Expr,
+    property: MemberProp
+) -> MemberExpr {
+    MemberExpr {
+        span: DUMMY_SP,
+        obj: Box::new(object),
+        prop: property
+    }
+}
+
+fn create_literal_boolean(bool_value: bool) -> Lit {
+    Lit::Bool(Bool {
+        span: DUMMY_SP,
+        value: bool_value
+    })
+}
+
+fn create_literal_string(string_value: &str) -> Lit {
+    Lit::Str(Str {
+        span: DUMMY_SP,
+        value: JsWord::from(string_value),
+        raw: create_string_raw(string_value)
+    })
+}
+
+fn create_literal_number(number_value: f64) -> Lit {
+    Lit::Num(Number {
+        span: DUMMY_SP,
+        value: number_value,
+        raw: Some(Atom::new(number_value.to_string()))
+    })
+}
+
+fn create_identifier(identifier_name: &str) -> Ident {
+    Ident::new(
+        JsWord::from(identifier_name),
+        DUMMY_SP
+    )
+}
+
+fn create_function(
+    params: Vec<Param>,
+    decorators: Vec<Decorator>,
+    body: Option<BlockStmt>,
+    is_generator: bool,
+    is_async: bool,
+    type_params: Option<Box<TsTypeParamDecl>>,
+    return_type: Option<Box<TsTypeAnn>>
+) -> Function {
+    Function {
+        params,
+        decorators,
+        span: DUMMY_SP,
+        body,
+        is_generator,
+        is_async,
+        type_params,
+        return_type
+    }
+}
+
+fn create_pat_ident(
+    id: Ident,
+    type_ann: Option<Box<TsTypeAnn>>
+) -> Pat {
+    Pat::Ident(BindingIdent {
+        id,
+        type_ann
+    })
+}
+
+fn create_param(
+    param_name: &str
+) -> Param {
+    Param {
+        span: DUMMY_SP,
+        decorators: vec![],
+        pat: create_pat_ident(
+            create_identifier(param_name),
+            None
+        )
+    }
+}
+
+fn create_block_statement(statements: Vec<Stmt>) -> BlockStmt {
+    BlockStmt {
+        span: DUMMY_SP,
+        stmts: statements
+    }
+}
+
+fn create_fn_declaration(
+    func_name: &str,
+    declare: bool,
+    function: Function
+) -> Stmt {
+    Stmt::Decl(Decl::Fn(FnDecl {
+        ident: create_identifier(func_name),
+        declare,
+        function: Box::new(function)
+    }))
+}
+
+fn create_fn_expression(
+    func_name: Option<&str>,
+    function: Function
+) -> Expr {
+    Expr::Fn(FnExpr {
+        ident: func_name.map(create_identifier),
+        function: Box::new(function)
+    })
+}
+
+fn create_arrow_expression(params: Vec<Pat>, body: BlockStmtOrExpr, is_async: bool) -> Expr {
+    Expr::Arrow(ArrowExpr {
+        span: DUMMY_SP,
+        params,
+        body: Box::new(body),
+        is_async,
+        is_generator: false,
+        type_params: None,
+        return_type: None
+    })
+}
+
+fn create_if_statement(
+    test: Expr,
+    cons: Stmt,
+    alt: Option<Box<Stmt>>
+) -> Stmt {
+    Stmt::If(IfStmt {
+        span: DUMMY_SP,
+        test: Box::new(test),
+        cons: Box::new(cons),
+        alt
+    })
+}
+
+fn create_return_statement(
+    arg: Option<Expr>
+) -> ReturnStmt {
+    ReturnStmt {
+        span: DUMMY_SP,
+        arg: arg.map(Box::new)
+    }
+}
+
+fn create_bin_expression(
     op: BinaryOp,
     left: Box<Expr>,
     right: Box<Expr>
@@ -333,108 +1638,1276 @@ fn create_bin_expression(
     })
 }
 
-fn create_unary_expression(
-    op: UnaryOp,
-    arg: Expr
-) -> Expr {
-    Expr::Unary(UnaryExpr {
-        span: DUMMY_SP,
-        op,
-        arg: Box::new(arg)
-    })
+fn create_unary_expression(
+    op: UnaryOp,
+    arg: Expr
+) -> Expr {
+    Expr::Unary(UnaryExpr {
+        span: DUMMY_SP,
+        op,
+        arg: Box::new(arg)
+    })
+}
+
+fn create_sequence_expression(exprs: Vec<Expr>) -> Expr {
+    Expr::Seq(SeqExpr {
+        span: DUMMY_SP,
+        exprs: exprs.into_iter().map(Box::new).collect()
+    })
+}
+
+fn create_assignment_expression(
+    op: AssignOp,
+    left: PatOrExpr,
+    right: Box<Expr>
+) -> Expr {
+    Expr::Assign(AssignExpr {
+        span: DUMMY_SP,
+        op,
+        left,
+        right
+    })
+}
+
+fn create_conditional_expression(
+    test: Expr,
+    cons: Expr,
+    alt: Expr
+) -> Expr {
+    Expr::Cond(CondExpr {
+        span: DUMMY_SP,
+        test: Box::new(test),
+        cons: Box::new(cons),
+        alt: Box::new(alt)
+    })
+}
+
+fn create_variable_declarator(
+    name: Pat,
+    init: Option<Box<Expr>>
+) -> VarDeclarator {
+    VarDeclarator {
+        span: DUMMY_SP,
+        name,
+        init,
+        definite: false
+    }
+}
+
+fn create_variable_declaration(
+    kind: VarDeclKind,
+    decls: Vec<VarDeclarator>
+) -> VarDecl {
+    VarDecl {
+        span: DUMMY_SP,
+        kind,
+        declare: false,
+        decls
+    }
+}
+
+fn create_for_in_statement(
+    left: ForHead,
+    right: Expr,
+    body: Stmt
+) -> Stmt {
+    Stmt::ForIn(ForInStmt {
+        span: DUMMY_SP,
+        left,
+        right: Box::new(right),
+        body: Box::new(body)
+    })
+}
+
+fn create_entity_name_expression(entity_name: &TsEntityName) -> Expr {
+    match entity_name {
+        TsEntityName::Ident(ident) => Expr::from(ident.clone()),
+        TsEntityName::TsQualifiedName(qualified_name) => Expr::from(create_member_expression(
+            create_entity_name_expression(&qualified_name.left),
+            &qualified_name.right.sym
+        ))
+    }
+}
+
+fn create_require_call_expression(module_path: &str) -> Expr {
+    create_call_expression(
+        Callee::Expr(Box::new(Expr::from(create_identifier("require")))),
+        vec![
+        create_argument_expr_or_spread(
+            Box::new(Expr::from(create_literal_string(module_path))),
+            false
+        )
+        ],
+        None
+    )
+}
+
+fn define_es_module_property(value: bool) -> Stmt {
+    create_expression_statement_as_stmt(
+        Box::new(create_call_expression(
+            create_callee_member_expression(
+                Expr::from(create_identifier("Object")),
+                "defineProperty"
+            ),
+            vec![
+            create_argument_expr_or_spread(
+                create_argument_identifier("exports", false),
+                false
+            ),
+            create_argument_expr_or_spread(
+                create_js_string_as_box_expr("__esModule"),
+                false
+            ),
+            ExprOrSpread::from(create_js_object_as_box_expr(
+                vec![
+                create_literal_prop(
+                    "value",
+                    false,
+                    create_literal_boolean(value)
+                )
+                ]
+            ))
+            ],
+            None
+        ))
+    )
+}
+
+fn define_export_assignment_by_identfier(
+    prop_name: &str,
+    prop_value: Ident
+) -> Stmt {
+    create_expression_statement_as_stmt(
+        Box::new(create_assignment_expression(
+            AssignOp::Assign,
+            PatOrExpr::Expr(Box::new(Expr::from(create_member_expression(
+                Expr::from(create_identifier("exports")),
+                prop_name
+            )))),
+            Box::new(Expr::from(prop_value))
+        ))
+    )
+}
+
+fn define_export_getter(
+    prop_name: &str,
+    prop_value: Expr
+) -> Stmt {
+    // Object.defineProperty(exports, "name", { enumerable: true, get: function () { return value; } })
+    // is the getter shape cjs-module-lexer detects as a named export.
+    create_expression_statement_as_stmt(
+        Box::new(create_call_expression(
+            create_callee_member_expression(
+                Expr::from(create_identifier("Object")),
+                "defineProperty"
+            ),
+            vec![
+            create_argument_expr_or_spread(
+                create_argument_identifier("exports", false),
+                false
+            ),
+            create_argument_expr_or_spread(
+                create_js_string_as_box_expr(prop_name),
+                false
+            ),
+            ExprOrSpread::from(create_js_object_as_box_expr(
+                vec![
+                create_literal_prop(
+                    "enumerable",
+                    false,
+                    create_literal_boolean(true)
+                ),
+                create_prop(
+                    "get",
+                    false,
+                    create_fn_expression(
+                        None,
+                        create_function(
+                            vec![],
+                            vec![],
+                            Some(create_block_statement(vec![
+                                Stmt::from(create_return_statement(Some(prop_value)))
+                            ])),
+                            false,
+                            false,
+                            None,
+                            None
+                        )
+                    )
+                )
+                ]
+            ))
+            ],
+            None
+        ))
+    )
+}
+
+fn define_export_pre_declaration(prop_name: &str) -> Stmt {
+    create_expression_statement_as_stmt(
+        Box::new(create_assignment_expression(
+            AssignOp::Assign,
+            PatOrExpr::Expr(Box::new(Expr::from(create_member_expression(
+                Expr::from(create_identifier("exports")),
+                prop_name
+            )))),
+            Box::new(create_unary_expression(
+                UnaryOp::Void,
+                Expr::Lit(create_literal_number(0.0))
+            ))
+        ))
+    )
 }
 
-fn create_assignment_expression(
-    op: AssignOp,
-    left: PatOrExpr,
-    right: Box<Expr>
-) -> Expr {
-    Expr::Assign(AssignExpr {
-        span: DUMMY_SP,
-        op,
-        left,
-        right
-    })
+fn define_module_exports_assignment(prop_value: Box<Expr>) -> Stmt {
+    create_expression_statement_as_stmt(
+        Box::new(create_assignment_expression(
+            AssignOp::Assign,
+            PatOrExpr::Expr(Box::new(Expr::from(create_member_expression(
+                Expr::from(create_identifier("module")),
+                "exports"
+            )))),
+            prop_value
+        ))
+    )
+}
+
+fn define_module_exports_default_property() -> Stmt {
+    let module_exports = Expr::from(create_member_expression(
+        Expr::from(create_identifier("module")),
+        "exports"
+    ));
+
+    create_expression_statement_as_stmt(
+        Box::new(create_assignment_expression(
+            AssignOp::Assign,
+            PatOrExpr::Expr(Box::new(Expr::from(create_member_expression(
+                module_exports.clone(),
+                "default"
+            )))),
+            Box::new(module_exports)
+        ))
+    )
+}
+
+fn define_merged_module_exports_assignment(default_value: Box<Expr>) -> Stmt {
+    // exports = module.exports = _merge_named_exports(value, exports)
+    // `exports` is rebound so later export assignments land on the default.
+    let merged_exports = create_call_expression(
+        Callee::Expr(Box::new(Expr::from(create_identifier("_merge_named_exports")))),
+        vec![
+        create_argument_expr_or_spread(default_value, false),
+        create_argument_expr_or_spread(create_argument_identifier("exports", false), false)
+        ],
+        None
+    );
+
+    create_expression_statement_as_stmt(
+        Box::new(create_assignment_expression(
+            AssignOp::Assign,
+            PatOrExpr::Pat(Box::new(create_pat_ident(create_identifier("exports"), None))),
+            Box::new(create_assignment_expression(
+                AssignOp::Assign,
+                PatOrExpr::Expr(Box::new(Expr::from(create_member_expression(
+                    Expr::from(create_identifier("module")),
+                    "exports"
+                )))),
+                Box::new(merged_exports)
+            ))
+        ))
+    )
+}
+
+fn define_merge_named_exports_function() -> Stmt {
+    // function _merge_named_exports(value, named) {
+    //     if (Object(value) !== value) throw new TypeError("...");
+    //     return Object.defineProperties(value, Object.getOwnPropertyDescriptors(named));
+    // }
+    // Literal primitives already fail the build; this catches the ones only
+    // known at runtime. Descriptors are copied so live binding getters keep working.
+    create_fn_declaration(
+        "_merge_named_exports",
+        false,
+        create_function(
+            vec![
+            create_param("value"),
+            create_param("named")
+            ],
+            vec![],
+            Some(create_block_statement(vec![
+                create_if_statement(
+                    create_bin_expression(
+                        BinaryOp::NotEqEq,
+                        Box::new(create_call_expression(
+                            Callee::Expr(Box::new(Expr::from(create_identifier("Object")))),
+                            vec![
+                            create_argument_expr_or_spread(create_argument_identifier("value", false), false)
+                            ],
+                            None
+                        )),
+                        Box::new(Expr::from(create_identifier("value")))
+                    ),
+                    Stmt::Throw(ThrowStmt {
+                        span: DUMMY_SP,
+                        arg: Box::new(Expr::New(NewExpr {
+                            span: DUMMY_SP,
+                            callee: Box::new(Expr::from(create_identifier("TypeError"))),
+                            args: Some(vec![
+                                create_argument_expr_or_spread(
+                                    create_js_string_as_box_expr("mergeNamedExports requires the default export to be an object or a function"),
+                                    false
+                                )
+                            ]),
+                            type_args: None
+                        }))
+                    }),
+                    None
+                ),
+                Stmt::from(create_return_statement(Some(
+                    create_call_expression(
+                        create_callee_member_expression(
+                            Expr::from(create_identifier("Object")),
+                            "defineProperties"
+                        ),
+                        vec![
+                        create_argument_expr_or_spread(create_argument_identifier("value", false), false),
+                        create_argument_expr_or_spread(
+                            Box::new(create_call_expression(
+                                create_callee_member_expression(
+                                    Expr::from(create_identifier("Object")),
+                                    "getOwnPropertyDescriptors"
+                                ),
+                                vec![
+                                create_argument_expr_or_spread(create_argument_identifier("named", false), false)
+                                ],
+                                None
+                            )),
+                            false
+                        )
+                        ],
+                        None
+                    )
+                )))
+            ])),
+            false,
+            false,
+            None,
+            None
+        )
+    )
+}
+
+fn define_export_assignment_by_literal_value(
+    prop_name: &str,
+    prop_value: Box<Expr>
+) -> Stmt {
+    create_expression_statement_as_stmt(
+        Box::new(create_assignment_expression(
+            AssignOp::Assign,
+            PatOrExpr::Expr(Box::new(Expr::from(create_member_expression(
+                Expr::from(create_identifier("exports")),
+                prop_name
+            )))),
+            prop_value
+        ))
+    )
+}
+
+fn define_export_star_function() -> Stmt {
+    //I'm so sorry if you are going to read this code.
+
+    create_fn_declaration(
+        "__exportStar",
+        false,
+        create_function(
+            vec![
+            create_param(
+                "from"
+            ),
+            create_param(
+                "to"
+            ),
+            ],
+            vec![],
+            Some(create_block_statement(
+                vec![
+                create_expression_statement_as_stmt(
+                    Box::new(create_call_expression(
+                        create_callee_member_expression(
+                            create_call_expression(
+                                create_callee_member_expression(
+                                    Expr::from(create_identifier("Object")),
+                                    "keys"
+                                ),
+                                vec![
+                                create_argument_expr_or_spread(
+                                    create_argument_identifier(
+                                        "from",
+                                        false
+                                    ),
+                                    false
+                                )
+                                ],
+                                None
+                            ),
+                            "forEach"
+                        ),
+                        vec![
+                        create_argument_expr_or_spread(
+                            Box::new(create_fn_expression(
+                                None,
+                                create_function(
+                                    vec![
+                                    create_param("k")
+                                    ],
+                                    vec![],
+                                    Some(create_block_statement(vec![
+                                        create_if_statement(
+                                            create_bin_expression(
+                                                BinaryOp::LogicalAnd,
+                                                Box::new(create_bin_expression(
+                                                    BinaryOp::NotEqEq,
+                                                    Box::new(Expr::from(create_identifier("k"))),
+                                                    Box::new(Expr::from(create_literal_string("default")))
+                                                )),
+                                                Box::new(create_unary_expression(
+                                                    UnaryOp::Bang,
+                                                    create_call_expression(
+                                                        create_callee_member_expression(
+                                                            Expr::from(create_member_expression(
+                                                                Expr::from(create_member_expression(
+                                                                    Expr::from(create_identifier("Object")),
+                                                                    "prototype"
+                                                                )),
+                                                                "hasOwnProperty"
+                                                            )),
+                                                            "call"
+                                                        ),
+                                                        vec![
+                                                        create_argument_expr_or_spread(
+                                                            Box::new(Expr::from(create_identifier("to"))),
+                                                            false
+                                                        ),
+                                                        create_argument_expr_or_spread(
+                                                            Box::new(Expr::from(create_identifier("k"))),
+                                                            false
+                                                        )
+                                                        ],
+                                                        None
+                                                    )
+                                                ))
+                                            ),
+                                            Stmt::from(create_block_statement(
+                                                vec![
+                                                create_expression_statement_as_stmt(
+                                                    Box::new(create_call_expression(
+                                                        create_callee_member_expression(
+                                                            Expr::from(create_identifier("Object")),
+                                                            "defineProperty"
+                                                        ),
+                                                        vec![
+                                                        create_argument_expr_or_spread(
+                                                            Box::new(Expr::from(create_identifier("to"))),
+                                                            false
+                                                        ),
+                                                        create_argument_expr_or_spread(
+                                                            Box::new(Expr::from(create_identifier("k"))),
+                                                            false
+                                                        ),
+                                                        ExprOrSpread::from(create_js_object_as_box_expr(
+                                                            vec![
+                                                            create_literal_prop(
+                                                                "enumerable",
+                                                                false,
+                                                                create_literal_boolean(true)
+                                                            ),
+                                                            create_prop(
+                                                                "get",
+                                                                false,
+                                                                create_fn_expression(
+                                                                    None,
+                                                                    create_function(
+                                                                        vec![],
+                                                                        vec![],
+                                                                        Some(create_block_statement(vec![
+                                                                            Stmt::from(create_return_statement(Some(
+                                                                                Expr::from(create_member_expression_by_key(
+                                                                                    Expr::from(create_identifier("from")),
+                                                                                    MemberProp::Computed(ComputedPropName {
+                                                                                        span: DUMMY_SP,
+                                                                                        expr: Box::new(Expr::from(create_identifier("k")))
+                                                                                    })
+                                                                                ))
+                                                                            )))
+                                                                            ])),
+                                                                            false,
+                                                                            false,
+                                                                            None,
+                                                                            None
+                                                                        )
+                                                                    )
+                                                                )
+                                                                ]
+                                                            ))
+                                                            ],
+                                                            None
+                                                        ))
+                                                    )
+                                                    ]
+                                                )),
+                                                None
+                                            ),
+                                            ],
+                                        )),
+                                        false,
+                                        false,
+                                        None,
+                                        None
+                                    )
+                                )),
+                                false
+                            )
+                            ],
+                            None
+                        ))
+                    ),
+
+                    Stmt::from(create_return_statement(
+                        Some(Expr::from(create_identifier("from")))
+                    ))
+                    ]
+                )),
+                false,
+                false,
+                None,
+                None
+            )
+        )
+}
+
+fn define_interop_require_default_function() -> Stmt {
+    // function _interop_require_default(obj) {
+    //     return obj && obj.__esModule ? obj : { default: obj };
+    // }
+    create_fn_declaration(
+        "_interop_require_default",
+        false,
+        create_function(
+            vec![
+            create_param("obj")
+            ],
+            vec![],
+            Some(create_block_statement(vec![
+                Stmt::from(create_return_statement(Some(
+                    create_conditional_expression(
+                        create_bin_expression(
+                            BinaryOp::LogicalAnd,
+                            Box::new(Expr::from(create_identifier("obj"))),
+                            Box::new(Expr::from(create_member_expression(
+                                Expr::from(create_identifier("obj")),
+                                "__esModule"
+                            )))
+                        ),
+                        Expr::from(create_identifier("obj")),
+                        *create_js_object_as_box_expr(vec![
+                            create_prop(
+                                "default",
+                                false,
+                                Expr::from(create_identifier("obj"))
+                            )
+                        ])
+                    )
+                )))
+            ])),
+            false,
+            false,
+            None,
+            None
+        )
+    )
+}
+
+fn define_interop_require_wildcard_function() -> Stmt {
+    // function _interop_require_wildcard(obj) {
+    //     if (obj && obj.__esModule) return obj;
+    //     var newObj = {};
+    //     if (obj != null) {
+    //         for (var key in obj) {
+    //             if (key !== "default" && Object.prototype.hasOwnProperty.call(obj, key)) newObj[key] = obj[key];
+    //         }
+    //     }
+    //     newObj.default = obj;
+    //     return newObj;
+    // }
+    let copy_property = create_if_statement(
+        create_bin_expression(
+            BinaryOp::LogicalAnd,
+            Box::new(create_bin_expression(
+                BinaryOp::NotEqEq,
+                Box::new(Expr::from(create_identifier("key"))),
+                Box::new(Expr::from(create_literal_string("default")))
+            )),
+            Box::new(create_call_expression(
+                create_callee_member_expression(
+                    Expr::from(create_member_expression(
+                        Expr::from(create_member_expression(
+                            Expr::from(create_identifier("Object")),
+                            "prototype"
+                        )),
+                        "hasOwnProperty"
+                    )),
+                    "call"
+                ),
+                vec![
+                create_argument_expr_or_spread(
+                    Box::new(Expr::from(create_identifier("obj"))),
+                    false
+                ),
+                create_argument_expr_or_spread(
+                    Box::new(Expr::from(create_identifier("key"))),
+                    false
+                )
+                ],
+                None
+            ))
+        ),
+        create_expression_statement_as_stmt(Box::new(create_assignment_expression(
+            AssignOp::Assign,
+            PatOrExpr::Expr(Box::new(Expr::from(create_member_expression_by_key(
+                Expr::from(create_identifier("newObj")),
+                MemberProp::Computed(ComputedPropName {
+                    span: DUMMY_SP,
+                    expr: Box::new(Expr::from(create_identifier("key")))
+                })
+            )))),
+            Box::new(Expr::from(create_member_expression_by_key(
+                Expr::from(create_identifier("obj")),
+                MemberProp::Computed(ComputedPropName {
+                    span: DUMMY_SP,
+                    expr: Box::new(Expr::from(create_identifier("key")))
+                })
+            )))
+        ))),
+        None
+    );
+
+    create_fn_declaration(
+        "_interop_require_wildcard",
+        false,
+        create_function(
+            vec![
+            create_param("obj")
+            ],
+            vec![],
+            Some(create_block_statement(vec![
+                create_if_statement(
+                    create_bin_expression(
+                        BinaryOp::LogicalAnd,
+                        Box::new(Expr::from(create_identifier("obj"))),
+                        Box::new(Expr::from(create_member_expression(
+                            Expr::from(create_identifier("obj")),
+                            "__esModule"
+                        )))
+                    ),
+                    Stmt::from(create_return_statement(Some(Expr::from(create_identifier("obj"))))),
+                    None
+                ),
+                Stmt::Decl(Decl::Var(Box::new(create_variable_declaration(
+                    VarDeclKind::Var,
+                    vec![
+                    create_variable_declarator(
+                        create_pat_ident(create_identifier("newObj"), None),
+                        Some(create_js_object_as_box_expr(vec![]))
+                    )
+                    ]
+                )))),
+                create_if_statement(
+                    create_bin_expression(
+                        BinaryOp::NotEq,
+                        Box::new(Expr::from(create_identifier("obj"))),
+                        Box::new(Expr::Lit(Lit::Null(Null { span: DUMMY_SP })))
+                    ),
+                    Stmt::from(create_block_statement(vec![
+                        create_for_in_statement(
+                            ForHead::VarDecl(Box::new(create_variable_declaration(
+                                VarDeclKind::Var,
+                                vec![
+                                create_variable_declarator(
+                                    create_pat_ident(create_identifier("key"), None),
+                                    None
+                                )
+                                ]
+                            ))),
+                            Expr::from(create_identifier("obj")),
+                            Stmt::from(create_block_statement(vec![copy_property]))
+                        )
+                    ])),
+                    None
+                ),
+                create_expression_statement_as_stmt(Box::new(create_assignment_expression(
+                    AssignOp::Assign,
+                    PatOrExpr::Expr(Box::new(Expr::from(create_member_expression(
+                        Expr::from(create_identifier("newObj")),
+                        "default"
+                    )))),
+                    Box::new(Expr::from(create_identifier("obj")))
+                ))),
+                Stmt::from(create_return_statement(Some(Expr::from(create_identifier("newObj")))))
+            ])),
+            false,
+            false,
+            None,
+            None
+        )
+    )
 }
 
-fn define_es_module_property(value: bool) -> Stmt {
-    create_expression_statement_as_stmt(
-        Box::new(create_call_expression(
-            create_callee_member_expression(
-                Expr::from(create_identifier("Object")),
-                "defineProperty"
-            ),
-            vec![
-            create_argument_expr_or_spread(
-                create_argument_identifier("exports", false),
-                false
-            ),
-            create_argument_expr_or_spread(
-                create_js_string_as_box_expr("__esModule"),
-                false
-            ),
-            ExprOrSpread::from(create_js_object_as_box_expr(
+impl ModuleExportAll {
+    fn write_module_header(&mut self, module_header: &mut ModuleHeader) {
+        if self.strict_mode && !self.contains_use_strict_declaration {
+            let use_strict = create_js_string_as_stmt("use strict");
+            module_header.preamble.push(ModuleItem::from(use_strict));
+            self.contains_use_strict_declaration = true;
+        }
+
+        if !self.contains_es_module_definition {
+            let define_es_module_value = match self.es_module_mode {
+                EsModuleMode::Loose => define_export_assignment_by_literal_value(
+                    "__esModule",
+                    create_literal_prop_value(create_literal_boolean(true))
+                ),
+                _ => define_es_module_property(true)
+            };
+
+            module_header.preamble.push(ModuleItem::from(define_es_module_value));
+            self.contains_es_module_definition = true;
+        }
+    }
+
+    fn write_interop_helper(&mut self, interop_helper: &str, module_header: &mut ModuleHeader) {
+        if interop_helper == "_interop_require_default" && !self.has_writed_interop_require_default {
+            module_header.helpers.push(ModuleItem::from(define_interop_require_default_function()));
+            self.has_writed_interop_require_default = true;
+        }
+
+        if interop_helper == "_interop_require_wildcard" && !self.has_writed_interop_require_wildcard {
+            module_header.helpers.push(ModuleItem::from(define_interop_require_wildcard_function()));
+            self.has_writed_interop_require_wildcard = true;
+        }
+    }
+
+    // The merged default receives the named exports' descriptors, including
+    // the `void 0` pre-declarations, so its properties are left alone.
+    fn exposes_default_object_properties(&self) -> bool {
+        self.export_default_object_properties && !self.merge_named_exports
+    }
+
+    fn define_local_export(
+        &self,
+        export_name: &str,
+        local: Ident,
+        live_bindings: &mut LiveBindingRewriter
+    ) -> Stmt {
+        if export_name == MODULE_EXPORTS_EXPORT_NAME {
+            return define_module_exports_assignment(Box::new(Expr::from(local)));
+        }
+
+        if let Some(export_getter) = self.define_live_export(export_name, &local, live_bindings) {
+            return export_getter;
+        }
+
+        define_export_assignment_by_identfier(export_name, local)
+    }
+
+    // Keeps `exports.<name>` in sync with a reassigned binding. The getter mode
+    // returns the getter to emit; the assignment mode registers the binding so
+    // every later write also updates the export.
+    fn define_live_export(
+        &self,
+        export_name: &str,
+        local: &Ident,
+        live_bindings: &mut LiveBindingRewriter
+    ) -> Option<Stmt> {
+        if !live_bindings.reassigned_identifiers.contains(&local.to_id()) {
+            return None;
+        }
+
+        match self.live_binding_mode {
+            LiveBindingMode::Getter => Some(define_export_getter(export_name, Expr::from(local.clone()))),
+            LiveBindingMode::Assignment => {
+                live_bindings.exported_bindings
+                    .entry(local.to_id())
+                    .or_default()
+                    .push(JsWord::from(export_name));
+
+                None
+            }
+        }
+    }
+
+    fn create_interop_require_expression(
+        &mut self,
+        module_path: &str,
+        has_namespace: bool,
+        has_default: bool,
+        has_named: bool,
+        module_header: &mut ModuleHeader
+    ) -> Expr {
+        let interop_helper = if has_namespace || (has_default && has_named) {
+            Some("_interop_require_wildcard")
+        } else if has_default {
+            Some("_interop_require_default")
+        } else {
+            None
+        };
+
+        match interop_helper {
+            Some(interop_helper) => {
+                self.write_interop_helper(interop_helper, module_header);
+
+                create_call_expression(
+                    Callee::Expr(Box::new(Expr::from(create_identifier(interop_helper)))),
+                    vec![
+                    create_argument_expr_or_spread(
+                        Box::new(create_require_call_expression(module_path)),
+                        false
+                    )
+                    ],
+                    None
+                )
+            },
+            None => create_require_call_expression(module_path)
+        }
+    }
+
+    fn convert_reexport_declaration(
+        &mut self,
+        named_export: &NamedExport,
+        module_identifiers: &mut ModuleIdentifierCollector,
+        module_header: &mut ModuleHeader
+    ) -> Vec<ModuleItem> {
+        let mut converted_items = Vec::new();
+
+        if named_export.type_only {
+            return converted_items;
+        }
+
+        let reexports: Vec<(JsWord, JsWord)> = named_export.specifiers
+            .iter()
+            .filter_map(|specifier| match specifier {
+                ExportSpecifier::Named(named_specifier) if !named_specifier.is_type_only => {
+                    let imported_name = get_module_export_name(&named_specifier.orig);
+                    let export_name = match &named_specifier.exported {
+                        Some(exported) => get_module_export_name(exported),
+                        None => imported_name.clone()
+                    };
+
+                    Some((imported_name, export_name))
+                },
+                _ => None
+            })
+            .collect();
+
+        let namespace_export_names: Vec<JsWord> = named_export.specifiers
+            .iter()
+            .filter_map(|specifier| specifier.as_namespace().map(|namespace_specifier| get_module_export_name(&namespace_specifier.name)))
+            .collect();
+
+        if reexports.is_empty() && namespace_export_names.is_empty() {
+            return converted_items;
+        }
+
+        if !self.has_writed_name_exports_module {
+            self.write_module_header(module_header);
+
+            self.has_writed_name_exports_module = true;
+        }
+
+        let module_path = named_export.src.as_ref().unwrap().value.to_string();
+        let is_json_module = is_json_module(named_export.with.as_deref());
+
+        for namespace_export_name in namespace_export_names {
+            // The namespace of a JSON module is { default: value }.
+            let require_expression = self.create_interop_require_expression(
+                &module_path,
+                !is_json_module,
+                is_json_module,
+                false,
+                module_header
+            );
+
+            if &*namespace_export_name == MODULE_EXPORTS_EXPORT_NAME {
+                converted_items.push(ModuleItem::from(define_module_exports_assignment(Box::new(require_expression))));
+            } else {
+                converted_items.push(ModuleItem::from(define_export_assignment_by_literal_value(
+                    &namespace_export_name,
+                    Box::new(require_expression)
+                )));
+            }
+
+            if needs_export_pre_declaration(&namespace_export_name) {
+                module_header.pre_declarations.push(namespace_export_name);
+            }
+        }
+
+        if reexports.is_empty() {
+            return converted_items;
+        }
+
+        let has_default = reexports.iter().any(|(imported_name, _)| &**imported_name == "default");
+        let has_named = reexports.iter().any(|(imported_name, _)| &**imported_name != "default");
+
+        let require_expression = match is_json_module {
+            true => create_require_call_expression(&module_path),
+            false => self.create_interop_require_expression(
+                &module_path,
+                false,
+                has_default,
+                has_named,
+                module_header
+            )
+        };
+        let module_identifier = create_identifier(&module_identifiers.create_unique_name(&module_path));
+
+        converted_items.push(ModuleItem::from(Stmt::Decl(Decl::Var(Box::new(
+            create_variable_declaration(
+                VarDeclKind::Const,
+                vec![
+                create_variable_declarator(
+                    create_pat_ident(module_identifier.clone(), None),
+                    Some(Box::new(require_expression))
+                )
+                ]
+            )
+        )))));
+
+        for (imported_name, export_name) in reexports {
+            let reexported_value = match (is_json_module, &*imported_name) {
+                (true, "default") => Expr::from(module_identifier.clone()),
+                (true, _) => {
+                    emit_json_named_import_error(named_export.span);
+                    continue;
+                },
+                (false, _) => Expr::from(create_member_expression(
+                    Expr::from(module_identifier.clone()),
+                    &imported_name
+                ))
+            };
+
+            if &*export_name == MODULE_EXPORTS_EXPORT_NAME {
+                converted_items.push(ModuleItem::from(define_module_exports_assignment(Box::new(reexported_value))));
+            } else if is_json_module {
+                // A JSON value never changes, and the lexer only detects getters returning a member.
+                converted_items.push(ModuleItem::from(define_export_assignment_by_literal_value(&export_name, Box::new(reexported_value))));
+            } else {
+                converted_items.push(ModuleItem::from(define_export_getter(&export_name, reexported_value)));
+            }
+
+            if needs_export_pre_declaration(&export_name) {
+                module_header.pre_declarations.push(export_name);
+            }
+        }
+
+        converted_items
+    }
+
+    fn convert_import_declaration(
+        &mut self,
+        import_declaration: &ImportDecl,
+        module_identifiers: &mut ModuleIdentifierCollector,
+        imported_bindings: &mut ImportedBindingRewriter,
+        module_header: &mut ModuleHeader
+    ) -> Vec<ModuleItem> {
+        let mut converted_items = Vec::new();
+
+        if import_declaration.type_only {
+            return converted_items;
+        }
+
+        self.write_module_header(module_header);
+
+        let module_path = import_declaration.src.value.to_string();
+
+        if import_declaration.specifiers.is_empty() {
+            converted_items.push(ModuleItem::from(create_expression_statement_as_stmt(
+                Box::new(create_require_call_expression(&module_path))
+            )));
+
+            return converted_items;
+        }
+
+        // Same elision rule as tsc: bindings never read as a value are types.
+        let specifiers: Vec<&ImportSpecifier> = import_declaration.specifiers
+            .iter()
+            .filter(|specifier| match specifier {
+                ImportSpecifier::Named(named_specifier) => !named_specifier.is_type_only,
+                _ => true
+            })
+            .filter(|specifier| module_identifiers.is_used_as_value(get_import_specifier_local(specifier)))
+            .collect();
+
+        if specifiers.is_empty() {
+            return converted_items;
+        }
+
+        let mut namespace_identifier = None;
+        let mut has_default_import = false;
+        let mut has_named_import = false;
+
+        for specifier in &specifiers {
+            match specifier {
+                ImportSpecifier::Namespace(namespace_specifier) => namespace_identifier = Some(namespace_specifier.local.clone()),
+                ImportSpecifier::Default(_) => has_default_import = true,
+                ImportSpecifier::Named(named_specifier) => match &named_specifier.imported {
+                    Some(imported) if &*get_module_export_name(imported) == "default" => has_default_import = true,
+                    _ => has_named_import = true
+                }
+            }
+        }
+
+        let is_json_module = is_json_module(import_declaration.with.as_deref());
+
+        if is_json_module && has_named_import {
+            emit_json_named_import_error(import_declaration.span);
+        }
+
+        let require_expression = match (is_json_module, &namespace_identifier) {
+            // The namespace of a JSON module is { default: value }.
+            (true, Some(_)) => self.create_interop_require_expression(&module_path, false, true, false, module_header),
+            (true, None) => create_require_call_expression(&module_path),
+            (false, _) => self.create_interop_require_expression(
+                &module_path,
+                namespace_identifier.is_some(),
+                has_default_import,
+                has_named_import,
+                module_header
+            )
+        };
+
+        // The default binding of a JSON module is the required value itself.
+        let json_default_identifier = specifiers
+            .iter()
+            .find_map(|specifier| match specifier {
+                ImportSpecifier::Default(default_specifier) => Some(&default_specifier.local),
+                ImportSpecifier::Named(named_specifier) => named_specifier.imported
+                    .as_ref()
+                    .filter(|imported| &*get_module_export_name(imported) == "default")
+                    .map(|_| &named_specifier.local),
+                ImportSpecifier::Namespace(_) => None
+            })
+            .filter(|_| is_json_module && namespace_identifier.is_none())
+            .cloned();
+
+        let module_identifier = match (namespace_identifier, &json_default_identifier) {
+            (Some(namespace_identifier), _) => namespace_identifier,
+            (None, Some(json_default_identifier)) => json_default_identifier.clone(),
+            (None, None) => create_identifier(&module_identifiers.create_unique_name(&module_path))
+        };
+
+        converted_items.push(ModuleItem::from(Stmt::Decl(Decl::Var(Box::new(
+            create_variable_declaration(
+                VarDeclKind::Const,
                 vec![
-                create_literal_prop(
-                    "value",
-                    false,
-                    create_literal_boolean(value)
+                create_variable_declarator(
+                    create_pat_ident(module_identifier.clone(), None),
+                    Some(Box::new(require_expression))
                 )
                 ]
-            ))
-            ],
-            None
-        ))
-    )
-}
+            )
+        )))));
+
+        // References to the bindings are rewritten into member accesses on the
+        // module object afterwards, so they stay live like ESM bindings.
+        for specifier in specifiers {
+            let (local, imported_name) = match specifier {
+                ImportSpecifier::Default(default_specifier) => (&default_specifier.local, JsWord::from("default")),
+                ImportSpecifier::Named(named_specifier) => (
+                    &named_specifier.local,
+                    match &named_specifier.imported {
+                        Some(imported) => get_module_export_name(imported),
+                        None => named_specifier.local.sym.clone()
+                    }
+                ),
+                ImportSpecifier::Namespace(_) => continue
+            };
 
-fn define_export_assignment_by_identfier(
-    prop_name: &str,
-    prop_value: &str
-) -> Stmt {
-    create_expression_statement_as_stmt(
-        Box::new(create_assignment_expression(
-            AssignOp::Assign,
-            PatOrExpr::Expr(Box::new(Expr::from(create_member_expression(
-                Expr::from(create_identifier("exports")),
-                &prop_name
-            )))),
-            Box::new(Expr::from(create_identifier(&prop_value)))
-        ))
-    )
-}
+            if json_default_identifier.is_some() {
+                continue;
+            }
 
-fn define_export_assignment_by_literal_value(
-    prop_name: &str,
-    prop_value: Box<Expr>
-) -> Stmt {
-    create_expression_statement_as_stmt(
-        Box::new(create_assignment_expression(
-            AssignOp::Assign,
-            PatOrExpr::Expr(Box::new(Expr::from(create_member_expression(
-                Expr::from(create_identifier("exports")),
-                &prop_name
-            )))),
-            prop_value
-        ))
-    )
-}
+            imported_bindings.imported_bindings.insert(
+                local.to_id(),
+                (module_identifier.clone(), imported_name)
+            );
+        }
+
+        converted_items
+    }
 
-impl ModuleExportAll {
     fn visit_mut_program_module(&mut self, module: &mut Module) {
         let old_has_writed_all_module = self.has_writed_all_module;
         let old_has_writed_name_exports_module = self.has_writed_name_exports_module;
 
+        let mut module_identifiers = ModuleIdentifierCollector::default();
+        module.visit_with(&mut module_identifiers);
+
+        let mut imported_bindings = ImportedBindingRewriter::default();
+
+        let mut reassignments = ReassignmentCollector::default();
+        module.visit_with(&mut reassignments);
+
+        let mut live_bindings = LiveBindingRewriter {
+            reassigned_identifiers: reassignments.reassigned_identifiers,
+            exported_bindings: HashMap::new(),
+            temporary_identifier: create_identifier(&module_identifiers.create_unique_name("tmp")),
+            has_used_temporary: false
+        };
+
+        let mut top_level_declarations = HashSet::new();
+        let mut top_level_functions = HashSet::new();
+        let mut top_level_imports = HashSet::new();
+
+        for node in &module.body {
+            for ident in get_declared_value_identifiers(node) {
+                top_level_declarations.insert(ident.to_id());
+            }
+
+            // Ambient functions and overload signatures are stripped, so they can't be hoisted.
+            let function_identifier = match node {
+                ModuleItem::Stmt(Stmt::Decl(Decl::Fn(function_declaration)))
+                | ModuleItem::ModuleDecl(ModuleDecl::ExportDecl(ExportDecl { decl: Decl::Fn(function_declaration), .. }))
+                    if !function_declaration.declare && function_declaration.function.body.is_some() => Some(&function_declaration.ident),
+                ModuleItem::ModuleDecl(ModuleDecl::ExportDefaultDecl(ExportDefaultDecl { decl: DefaultDecl::Fn(function_expression), .. }))
+                    if function_expression.function.body.is_some() => function_expression.ident.as_ref(),
+                _ => None
+            };
+
+            if let Some(function_identifier) = function_identifier {
+                top_level_functions.insert(function_identifier.to_id());
+            }
+
+            if let ModuleItem::ModuleDecl(ModuleDecl::Import(import_declaration)) = node {
+                for specifier in &import_declaration.specifiers {
+                    top_level_imports.insert(get_import_specifier_local(specifier).to_id());
+                }
+            }
+        }
+
+        let expected_exports = match self.verify {
+            true => get_expected_export_names(
+                &module.body,
+                &top_level_declarations,
+                &top_level_imports,
+                self.exposes_default_object_properties()
+            ),
+            false => ExpectedExports::default()
+        };
+
+        let mut pending_export_assignments: Vec<(Ident, JsWord)> = Vec::new();
+
+        let only_default_export = has_only_default_export(&module.body);
+
+        let mut top_level_await = TopLevelAwaitFinder::default();
+        module.visit_with(&mut top_level_await);
+
+        let top_level_await_span = top_level_await.span;
+
+        if let (Some(span), TopLevelAwaitMode::Error) = (top_level_await_span, self.top_level_await_mode) {
+            HANDLER.with(|handler| {
+                handler
+                    .struct_span_err(
+                        span,
+                        "Top-level await can't be compiled to CommonJS, set topLevelAwait to \"promise\" to wrap the module in an async function"
+                    )
+                    .emit()
+            });
+        }
+
+        // In the async wrapper the default export is only known after require()
+        // has returned, too late to replace module.exports.
+        let replaces_module_exports = (self.merge_named_exports && module.body.iter().any(is_default_export))
+            || (self.add_module_exports && only_default_export);
+
+        if let (Some(span), TopLevelAwaitMode::Promise, true) = (top_level_await_span, self.top_level_await_mode, replaces_module_exports) {
+            HANDLER.with(|handler| {
+                handler
+                    .struct_span_err(
+                        span,
+                        "mergeNamedExports and addModuleExports can't replace module.exports in a module using top-level await"
+                    )
+                    .emit()
+            });
+        }
+
+        let mut module_header = ModuleHeader::default();
+
+        let export_assignment = module.body
+            .iter()
+            .find_map(|node| node.as_module_decl()?.as_ts_export_assignment());
+
+        if let Some(export_assignment) = export_assignment {
+            if module.body.iter().any(has_value_export) {
+                HANDLER.with(|handler| {
+                    handler
+                        .struct_span_err(
+                            export_assignment.span,
+                            "An export assignment cannot be used in a module with other exported elements"
+                        )
+                        .emit()
+                });
+            }
+        }
+
+        // A flag that must not be written is handled as if it already was.
+        // `export =` replaces module.exports, so like tsc it gets no flag.
+        let skips_es_module_definition = match self.es_module_mode {
+            _ if export_assignment.is_some() => true,
+            EsModuleMode::Never => true,
+            EsModuleMode::OnlyWhenDefaultExport => !module.body.iter().any(is_default_export),
+            _ => false
+        };
+
+        if skips_es_module_definition {
+            self.contains_es_module_definition = true;
+        }
+
+        // The directive prologue ("use strict", "use client", ...) only has
+        // meaning as the leading statements, so it stays at the very top.
+        let prologue_length = module.body
+            .iter()
+            .take_while(|node| get_directive_value(node).is_some())
+            .count();
+
+        for directive in module.body.drain(..prologue_length) {
+            if get_directive_value(&directive).map_or(false, |value| &**value == "use strict") {
+                self.contains_use_strict_declaration = true;
+            }
+
+            module_header.preamble.push(directive);
+        }
+
         let mut updated_body = Vec::new();
 
         for node in &mut *module.body {
             match node {
                 ModuleItem::ModuleDecl(var) => {
+                    if var.is_import() {
+                        let mut converted_items = self.convert_import_declaration(
+                            var.as_import().unwrap(),
+                            &mut module_identifiers,
+                            &mut imported_bindings,
+                            &mut module_header
+                        );
 
-                    if var.is_export_all() {
-
+                        updated_body.append(&mut converted_items);
+                    } else if var.is_export_all() {
                         let module_name_or_module_path = var.as_export_all().unwrap().src.value.to_string();
 
+                        // A JSON module has no named exports to re-export.
+                        if is_json_module(var.as_export_all().unwrap().with.as_deref()) {
+                            continue;
+                        }
+
                         //Attention, from now on the code will not be documented
                         //There's no going back from now on
                         //I wish you luck
@@ -466,391 +2939,479 @@ impl ModuleExportAll {
                             }))
                         );
 
-                        if self.has_writed_all_module == false {
-                            if self.contains_use_strict_declaration == false {
-                                let use_strict = create_js_string_as_stmt("use strict");
-                                updated_body.push(ModuleItem::from(use_strict));
-                                self.contains_use_strict_declaration = true;
-                            }
-
-                            if self.contains_es_module_definition == false {
-                                let define_es_module_value = define_es_module_property(true);
-                                updated_body.push(ModuleItem::from(define_es_module_value));
-                                self.contains_es_module_definition = true;
-                            }
-
-                            //I'm so sorry if you are going to read this code.
+                        if !self.has_writed_all_module {
+                            self.write_module_header(&mut module_header);
 
-                            let define_export_star_func = create_fn_declaration(
-                                "__exportStar",
-                                false,
-                                create_function(
-                                    vec![
-                                    create_param(
-                                        "from"
-                                    ),
-                                    create_param(
-                                        "to"
-                                    ),
-                                    ],
-                                    vec![],
-                                    Some(create_block_statement(
-                                        vec![
-                                        create_expression_statement_as_stmt(
-                                            Box::new(create_call_expression(
-                                                create_callee_member_expression(
-                                                    create_call_expression(
-                                                        create_callee_member_expression(
-                                                            Expr::from(create_identifier("Object")),
-                                                            "keys"
-                                                        ),
-                                                        vec![
-                                                        create_argument_expr_or_spread(
-                                                            create_argument_identifier(
-                                                                "from",
-                                                                false
-                                                            ),
-                                                            false
-                                                        )
-                                                        ],
-                                                        None
-                                                    ),
-                                                    "forEach"
-                                                ),
-                                                vec![
-                                                create_argument_expr_or_spread(
-                                                    Box::new(create_fn_expression(
-                                                        None,
-                                                        create_function(
-                                                            vec![
-                                                            create_param("k")
-                                                            ],
-                                                            vec![],
-                                                            Some(create_block_statement(vec![
-                                                                create_if_statement(
-                                                                    create_bin_expression(
-                                                                        BinaryOp::LogicalAnd,
-                                                                        Box::new(create_bin_expression(
-                                                                            BinaryOp::NotEqEq,
-                                                                            Box::new(Expr::from(create_identifier("k"))),
-                                                                            Box::new(Expr::from(create_literal_string("default")))
-                                                                        )),
-                                                                        Box::new(create_unary_expression(
-                                                                            UnaryOp::Bang,
-                                                                            create_call_expression(
-                                                                                create_callee_member_expression(
-                                                                                    Expr::from(create_member_expression(
-                                                                                        Expr::from(create_member_expression(
-                                                                                            Expr::from(create_identifier("Object")),
-                                                                                            "prototype"
-                                                                                        )),
-                                                                                        "hasOwnProperty"
-                                                                                    )),
-                                                                                    "call"
-                                                                                ),
-                                                                                vec![
-                                                                                create_argument_expr_or_spread(
-                                                                                    Box::new(Expr::from(create_identifier("to"))),
-                                                                                    false
-                                                                                ),
-                                                                                create_argument_expr_or_spread(
-                                                                                    Box::new(Expr::from(create_identifier("k"))),
-                                                                                    false
-                                                                                )
-                                                                                ],
-                                                                                None
-                                                                            )
-                                                                        ))
-                                                                    ),
-                                                                    Stmt::from(create_block_statement(
-                                                                        vec![
-                                                                        create_expression_statement_as_stmt(
-                                                                            Box::new(create_call_expression(
-                                                                                create_callee_member_expression(
-                                                                                    Expr::from(create_identifier("Object")),
-                                                                                    "defineProperty"
-                                                                                ),
-                                                                                vec![
-                                                                                create_argument_expr_or_spread(
-                                                                                    Box::new(Expr::from(create_identifier("to"))),
-                                                                                    false
-                                                                                ),
-                                                                                create_argument_expr_or_spread(
-                                                                                    Box::new(Expr::from(create_identifier("k"))),
-                                                                                    false
-                                                                                ),
-                                                                                ExprOrSpread::from(create_js_object_as_box_expr(
-                                                                                    vec![
-                                                                                    create_literal_prop(
-                                                                                        "enumerable",
-                                                                                        false,
-                                                                                        create_literal_boolean(true)
-                                                                                    ),
-                                                                                    create_prop(
-                                                                                        "get",
-                                                                                        false,
-                                                                                        create_fn_expression(
-                                                                                            None,
-                                                                                            create_function(
-                                                                                                vec![],
-                                                                                                vec![],
-                                                                                                Some(create_block_statement(vec![
-                                                                                                    Stmt::from(create_return_statement(Some(
-                                                                                                        Expr::from(create_member_expression_by_key(
-                                                                                                            Expr::from(create_identifier("from")),
-                                                                                                            MemberProp::Computed(ComputedPropName {
-                                                                                                                span: DUMMY_SP,
-                                                                                                                expr: Box::new(Expr::from(create_identifier("k")))
-                                                                                                            })
-                                                                                                        ))
-                                                                                                    )))
-                                                                                                    ])),
-                                                                                                    false,
-                                                                                                    false,
-                                                                                                    None,
-                                                                                                    None
-                                                                                                )
-                                                                                            )
-                                                                                        )
-                                                                                        ]
-                                                                                    ))
-                                                                                    ],
-                                                                                    None
-                                                                                ))
-                                                                            )
-                                                                            ]
-                                                                        )),
-                                                                        None
-                                                                    ),
-                                                                    ],
-                                                                )),
-                                                                false,
-                                                                false,
-                                                                None,
-                                                                None
-                                                            )
-                                                        )),
-                                                        false
-                                                    )
-                                                    ],
-                                                    None
-                                                ))
-                                            ),
+                            module_header.helpers.push(ModuleItem::from(define_export_star_function()));
 
-                                            Stmt::from(create_return_statement(
-                                                Some(Expr::from(create_identifier("from")))
-                                            ))
-                                            ]
-                                        )),
-                                        false,
-                                        false,
-                                        None,
-                                        None
-                                    )
-                                );
+                            self.has_writed_all_module = true;
+                        }
 
-                                updated_body.push(ModuleItem::from(define_export_star_func));
+                        updated_body.push(ModuleItem::from(__export_star));
+                    } else if var.is_export_decl() {
+                        if !self.has_writed_name_exports_module {
+                            self.write_module_header(&mut module_header);
 
-                                self.has_writed_all_module = true;
-                            }
+                            self.has_writed_name_exports_module = true;
+                        }
 
-                            updated_body.push(ModuleItem::from(__export_star));
+                        let node_declaration = &var.as_export_decl().unwrap().decl;
+                        // Empty for types, ambient declarations and overload signatures.
+                        let export_identifiers = get_declaration_value_identifiers(node_declaration);
 
-                        }
-                        if var.is_export_decl() {
+                        updated_body.push(ModuleItem::from(Stmt::Decl(node_declaration.clone())));
 
-                            if self.has_writed_name_exports_module == false {
-                                if self.contains_use_strict_declaration == false {
-                                    let use_strict = create_js_string_as_stmt("use strict");
-                                    updated_body.push(ModuleItem::from(use_strict));
-                                    self.contains_use_strict_declaration = true;
+                        match node_declaration {
+                            Decl::Fn(_) => {
+                                for export_ident in export_identifiers {
+                                    module_header.hoisted_exports.push(ModuleItem::from(self.define_local_export(&export_ident.sym, export_ident.clone(), &mut live_bindings)))
+                                }
+                            },
+                            Decl::Var(var_declaration) => {
+                                module_header.pre_declarations.extend(export_identifiers.into_iter().map(|ident| ident.sym));
+
+                                let mut initialized_identifiers = Vec::new();
+                                let mut uninitialized_identifiers = Vec::new();
+
+                                for declarator in &var_declaration.decls {
+                                    match declarator.init {
+                                        _ if var_declaration.declare => {},
+                                        Some(_) => collect_pat_identifiers(&declarator.name, &mut initialized_identifiers),
+                                        None => collect_pat_identifiers(&declarator.name, &mut uninitialized_identifiers)
+                                    }
                                 }
 
-                                if self.contains_es_module_definition == false {
-                                    let define_es_module_value = define_es_module_property(true);
-                                    updated_body.push(ModuleItem::from(define_es_module_value));
-                                    self.contains_es_module_definition = true;
+                                for export_ident in initialized_identifiers {
+                                    updated_body.push(ModuleItem::from(self.define_local_export(&export_ident.sym, export_ident.clone(), &mut live_bindings)))
                                 }
 
-                                self.has_writed_name_exports_module = true;
+                                // `exports.x = void 0` already holds the initial value, but later
+                                // writes still have to reach the export.
+                                for export_ident in uninitialized_identifiers {
+                                    if let Some(export_getter) = self.define_live_export(&export_ident.sym, &export_ident, &mut live_bindings) {
+                                        updated_body.push(ModuleItem::from(export_getter));
+                                    }
+                                }
+                            },
+                            _ => {
+                                for export_ident in export_identifiers {
+                                    module_header.pre_declarations.push(export_ident.sym.clone());
+                                    updated_body.push(ModuleItem::from(self.define_local_export(&export_ident.sym, export_ident.clone(), &mut live_bindings)))
+                                }
                             }
+                        };
+                    } else if var.is_export_named() && var.as_export_named().unwrap().src.is_some() {
+                        let mut converted_items = self.convert_reexport_declaration(
+                            var.as_export_named().unwrap(),
+                            &mut module_identifiers,
+                            &mut module_header
+                        );
 
-                            let node_declaration = &var.as_export_decl().unwrap().decl;
+                        updated_body.append(&mut converted_items);
+                    } else if var.is_export_named() {
+                        let named_export = var.as_export_named().unwrap();
 
-                            let export_names_or_nothing = match node_declaration.clone() {
-                                Decl::Class(class_node) => Some(vec![class_node.ident.sym]),
-                                Decl::Fn(function_node) => Some(vec![function_node.ident.sym]),
-                                Decl::Var(var_node) => {
-                                    let mut names = Vec::new();
+                        if named_export.type_only {
+                            continue;
+                        }
 
-                                    for declaration in var_node.decls {
-                                        names.push(declaration.name.as_ident().unwrap().id.sym.clone())
-                                    }
+                        if !self.has_writed_name_exports_module {
+                            self.write_module_header(&mut module_header);
 
-                                    Some(names)
-                                },
-                                _ => None
+                            self.has_writed_name_exports_module = true;
+                        }
+
+                        for specifier in &named_export.specifiers {
+                            let named_specifier = match specifier {
+                                ExportSpecifier::Named(named_specifier) if !named_specifier.is_type_only => named_specifier,
+                                _ => continue
                             };
 
-                            match export_names_or_nothing {
-                                Some(export_names) => {
-                                    for export_name in export_names {
-                                        let void_0_statement = create_expression_statement_as_stmt(
-                                            Box::new(create_assignment_expression(
-                                                AssignOp::Assign,
-                                                PatOrExpr::Expr(Box::new(Expr::from(create_member_expression(
-                                                    Expr::from(create_identifier("exports")),
-                                                    &export_name
-                                                )))),
-                                                Box::new(create_unary_expression(
-                                                    UnaryOp::Void,
-                                                    Expr::Lit(Lit::Num(Number {
-                                                        span: DUMMY_SP,
-                                                        value: f64::from(0),
-                                                        raw: Some(Atom::from("0"))
-                                                    }))
-                                                ))
-                                            ))
-                                        );
+                            let local = match &named_specifier.orig {
+                                ModuleExportName::Ident(ident) => ident,
+                                ModuleExportName::Str(_) => continue
+                            };
 
+                            let export_name = match &named_specifier.exported {
+                                Some(exported) => get_module_export_name(exported),
+                                None => local.sym.clone()
+                            };
 
-                                        updated_body.push(ModuleItem::from(void_0_statement));
-                                    }
-                                },
-                                _ => {}
+                            if top_level_functions.contains(&local.to_id()) {
+                                module_header.hoisted_exports.push(ModuleItem::from(
+                                    self.define_local_export(&export_name, local.clone(), &mut live_bindings)
+                                ));
+
+                                continue;
+                            } else if top_level_declarations.contains(&local.to_id()) {
+                                pending_export_assignments.push((local.clone(), export_name.clone()));
+                            } else if top_level_imports.contains(&local.to_id()) {
+                                // addModuleExports and mergeNamedExports look for the
+                                // `exports.default = ...` statement, so it stays an assignment.
+                                let is_replaced_default = &*export_name == "default"
+                                    && (self.add_module_exports || self.merge_named_exports);
+
+                                // Imported bindings are rewritten to `_a.foo` afterwards, so the
+                                // getter keeps reading the live value during circular requires.
+                                let export_statement = match &*export_name == MODULE_EXPORTS_EXPORT_NAME || is_replaced_default {
+                                    true => self.define_local_export(&export_name, local.clone(), &mut live_bindings),
+                                    false => define_export_getter(&export_name, Expr::Ident(local.clone()))
+                                };
+
+                                updated_body.push(ModuleItem::from(export_statement));
+                            } else {
+                                // Types and ambient declarations have nothing to export at runtime.
+                                continue;
                             }
 
-                            match node_declaration.clone() {
-                                Decl::Class(class_declaration) => {
-                                    let export_name = class_declaration.ident.sym.clone();
-
-                                    updated_body.push(ModuleItem::from(Stmt::Decl(Decl::from(class_declaration))));
-                                    updated_body.push(ModuleItem::from(define_export_assignment_by_identfier(&export_name, &export_name)))
-                                },
-                                Decl::Fn(function_declaration) => {
-                                    let export_name = function_declaration.ident.sym.clone();
-
-                                    updated_body.push(ModuleItem::from(Stmt::Decl(Decl::from(function_declaration))));
-                                    updated_body.push(ModuleItem::from(define_export_assignment_by_identfier(&export_name, &export_name)))
-                                },
-                                Decl::Var(var_declaration) => {
-
-                                    for ele in var_declaration.decls {
-                                        let export_name = ele.name.as_ident().unwrap().sym.clone();
-
-
-                                        match ele.init {
-                                            Some(init) => {
-                                                updated_body.push(ModuleItem::from(create_expression_statement_as_stmt(
-                                                    Box::new(create_assignment_expression(
-                                                        AssignOp::Assign,
-                                                        PatOrExpr::Expr(Box::new(Expr::from(create_member_expression(
-                                                            Expr::from(create_identifier("exports")),
-                                                            &export_name
-                                                        )))),
-                                                        init
-                                                    ))
-                                                )))
-                                            },
-                                            _ => {}
-                                        }
-                                    };
-                                },
-                                _ => {}
-                            };
+                            if needs_export_pre_declaration(&export_name) {
+                                module_header.pre_declarations.push(export_name);
+                            }
                         }
-                        if var.is_export_default_decl() {
-                            if self.has_writed_name_exports_module == false {
-                                if self.contains_use_strict_declaration == false {
-                                    let use_strict = create_js_string_as_stmt("use strict");
-                                    updated_body.push(ModuleItem::from(use_strict));
-                                    self.contains_use_strict_declaration = true;
-                                }
+                    } else if var.is_export_default_decl() {
+                        if !self.has_writed_name_exports_module {
+                            self.write_module_header(&mut module_header);
 
-                                if self.contains_es_module_definition == false {
-                                    let define_es_module_value = define_es_module_property(true);
-                                    updated_body.push(ModuleItem::from(define_es_module_value));
-                                    self.contains_es_module_definition = true;
-                                }
+                            self.has_writed_name_exports_module = true;
+                        }
 
-                                self.has_writed_name_exports_module = true;
-                            }
+                        let node_declaration = var.as_export_default_decl().unwrap();
 
-                            match var.as_export_default_decl() {
-                                Some(node_declaration) => {
-                                    match node_declaration.clone().decl {
-                                        DefaultDecl::Class(class_declaration) => {
-                                            let export_name = match class_declaration.ident.clone() {
-                                                Some(ident) => ident.sym.clone(),
-                                                _ => {
-                                                    self.class_default_number = self.class_default_number + 1;
-                                                    JsWord::from(format!("default_class_{}", self.class_default_number))
-                                                }
-                                            };
-
-                                            updated_body.push(ModuleItem::from(Stmt::Decl(Decl::Class(ClassDecl {
-                                                ident: create_identifier(&export_name),
-                                                declare: false,
-                                                class: class_declaration.class
-                                            }))));
-                                            updated_body.push(ModuleItem::from(define_export_assignment_by_identfier("default", &export_name)))
-                                        },
-                                        DefaultDecl::Fn(function_declaration) => {
-                                            let export_name = match function_declaration.ident.clone() {
-                                                Some(ident) => ident.sym.clone(),
-                                                _ => {
-                                                    self.function_default_number = self.function_default_number + 1;
-                                                    JsWord::from(format!("default_function_{}", self.function_default_number))
-                                                }
-                                            };
-
-                                            updated_body.push(ModuleItem::from(Stmt::Decl(Decl::Fn(FnDecl {
-                                                ident: create_identifier(&export_name),
-                                                declare: false,
-                                                function: function_declaration.function
-                                            }))));
-                                            updated_body.push(ModuleItem::from(define_export_assignment_by_identfier("default", &export_name)))
-                                        }
-                                        _ => {}
+                        match node_declaration.clone().decl {
+                            DefaultDecl::Class(class_declaration) => {
+                                let export_ident = match class_declaration.ident.clone() {
+                                    Some(ident) => ident,
+                                    _ => {
+                                        self.class_default_number += 1;
+                                        create_identifier(&format!("default_class_{}", self.class_default_number))
                                     }
+                                };
+
+                                updated_body.push(ModuleItem::from(Stmt::Decl(Decl::Class(ClassDecl {
+                                    ident: export_ident.clone(),
+                                    declare: false,
+                                    class: class_declaration.class
+                                }))));
+                                updated_body.push(ModuleItem::from(self.define_local_export("default", export_ident, &mut live_bindings)))
+                            },
+                            DefaultDecl::Fn(function_declaration) => {
+                                let export_ident = match function_declaration.ident.clone() {
+                                    Some(ident) => ident,
+                                    _ => {
+                                        self.function_default_number += 1;
+                                        create_identifier(&format!("default_function_{}", self.function_default_number))
+                                    }
+                                };
+
+                                let is_overload_signature = function_declaration.function.body.is_none();
+
+                                updated_body.push(ModuleItem::from(Stmt::Decl(Decl::Fn(FnDecl {
+                                    ident: export_ident.clone(),
+                                    declare: false,
+                                    function: function_declaration.function
+                                }))));
+
+                                if !is_overload_signature {
+                                    module_header.hoisted_exports.push(ModuleItem::from(self.define_local_export("default", export_ident, &mut live_bindings)))
                                 }
-                                None => {}
+                            },
+                            DefaultDecl::TsInterfaceDecl(interface_declaration) => {
+                                updated_body.push(ModuleItem::from(Stmt::Decl(Decl::TsInterface(interface_declaration))))
                             }
                         }
-                        if var.is_export_default_expr() {
-                            if self.has_writed_name_exports_module == false {
-                                if self.contains_use_strict_declaration == false {
-                                    let use_strict = create_js_string_as_stmt("use strict");
-                                    updated_body.push(ModuleItem::from(use_strict));
-                                    self.contains_use_strict_declaration = true;
-                                }
+                    } else if var.is_export_default_expr() {
+                        if !self.has_writed_name_exports_module {
+                            self.write_module_header(&mut module_header);
 
-                                if self.contains_es_module_definition == false {
-                                    let define_es_module_value = define_es_module_property(true);
-                                    updated_body.push(ModuleItem::from(define_es_module_value));
-                                    self.contains_es_module_definition = true;
-                                }
+                            self.has_writed_name_exports_module = true;
+                        }
+
+                        let node_expression = var.as_export_default_expr().unwrap();
+
+                        updated_body.push(ModuleItem::from(define_export_assignment_by_literal_value(
+                            "default",
+                            node_expression.expr.clone()
+                        )));
+
+                        // addModuleExports replaces module.exports with the object, so the
+                        // properties are assigned there to stay visible to cjs-module-lexer.
+                        let replaces_module_exports = self.add_module_exports && only_default_export;
 
-                                self.has_writed_name_exports_module = true;
+                        if let (true, Expr::Object(default_object)) = (self.exposes_default_object_properties(), node_expression.expr.unwrap_parens()) {
+                            let exports_object = match replaces_module_exports {
+                                true => Expr::from(create_member_expression(Expr::from(create_identifier("module")), "exports")),
+                                false => Expr::from(create_identifier("exports"))
+                            };
+
+                            for property_name in get_object_literal_property_names(default_object) {
+                                // exports.parse = exports.default.parse
+                                updated_body.push(ModuleItem::from(create_expression_statement_as_stmt(
+                                    Box::new(create_assignment_expression(
+                                        AssignOp::Assign,
+                                        PatOrExpr::Expr(Box::new(Expr::from(create_member_expression(
+                                            exports_object.clone(),
+                                            &property_name
+                                        )))),
+                                        Box::new(Expr::from(create_member_expression(
+                                            Expr::from(create_member_expression(exports_object.clone(), "default")),
+                                            &property_name
+                                        )))
+                                    ))
+                                )));
+
+                                if !replaces_module_exports {
+                                    module_header.pre_declarations.push(property_name);
+                                }
                             }
+                        }
+                    } else if var.is_ts_export_assignment() {
+                        self.write_module_header(&mut module_header);
 
-                            let node_expression = var.as_export_default_expr().unwrap();
+                        let export_assignment = var.as_ts_export_assignment().unwrap();
 
+                        updated_body.push(ModuleItem::from(define_module_exports_assignment(export_assignment.expr.clone())))
+                    } else if var.is_ts_import_equals() {
+                        let import_equals = var.as_ts_import_equals().unwrap();
+
+                        // Same elision rule as for import declarations.
+                        if import_equals.is_type_only || (!import_equals.is_export && !module_identifiers.is_used_as_value(&import_equals.id)) {
+                            continue;
+                        }
+
+                        self.write_module_header(&mut module_header);
+
+                        let imported_value = match &import_equals.module_ref {
+                            TsModuleRef::TsExternalModuleRef(external_module) => create_require_call_expression(&external_module.expr.value),
+                            TsModuleRef::TsEntityName(entity_name) => create_entity_name_expression(entity_name)
+                        };
+
+                        if import_equals.is_export {
+                            module_header.pre_declarations.push(import_equals.id.sym.clone());
+                        }
+
+                        if import_equals.is_export && !module_identifiers.is_used_as_value(&import_equals.id) {
                             updated_body.push(ModuleItem::from(define_export_assignment_by_literal_value(
-                                "default",
-                                node_expression.expr.clone()
-                            )))
+                                &import_equals.id.sym,
+                                Box::new(imported_value)
+                            )));
+
+                            continue;
                         }
-                    },
-                    _ => {
 
+                        updated_body.push(ModuleItem::from(Stmt::Decl(Decl::Var(Box::new(
+                            create_variable_declaration(
+                                VarDeclKind::Const,
+                                vec![
+                                create_variable_declarator(
+                                    create_pat_ident(import_equals.id.clone(), None),
+                                    Some(Box::new(imported_value))
+                                )
+                                ]
+                            )
+                        )))));
+
+                        if import_equals.is_export {
+                            updated_body.push(ModuleItem::from(define_export_assignment_by_identfier(
+                                &import_equals.id.sym,
+                                import_equals.id.clone()
+                            )));
+                        }
+                    } else if var.is_ts_namespace_export() {
+                        // `export as namespace` only declares a UMD global for type checking.
+                        continue;
+                    } else {
+                        updated_body.push(ModuleItem::ModuleDecl(var.clone()))
                     }
+                },
+                ModuleItem::Stmt(_) => updated_body.push(node.clone())
+            }
+        }
+
+        // Local export specifiers are assigned right after their declaration,
+        // so the value is never read while it is still in its TDZ.
+        if !pending_export_assignments.is_empty() {
+            let mut body_with_export_assignments = Vec::new();
+
+            for node in updated_body {
+                let declared_identifiers = get_declared_value_identifiers(&node);
+
+                body_with_export_assignments.push(node);
+
+                for declared_ident in declared_identifiers {
+                    pending_export_assignments.retain(|(local, export_name)| {
+                        if local.to_id() != declared_ident.to_id() {
+                            return true;
+                        }
+
+                        body_with_export_assignments.push(ModuleItem::from(
+                            self.define_local_export(export_name, local.clone(), &mut live_bindings)
+                        ));
+
+                        false
+                    });
+                }
+            }
+
+            updated_body = body_with_export_assignments;
+        }
+
+        if !live_bindings.exported_bindings.is_empty() {
+            updated_body.visit_mut_with(&mut live_bindings);
+        }
+
+        if !imported_bindings.imported_bindings.is_empty() {
+            updated_body.visit_mut_with(&mut imported_bindings);
+        }
+
+        updated_body.visit_mut_with(&mut ImportMetaRewriter {
+            options: self.import_meta
+        });
+
+        if self.dynamic_import_mode != DynamicImportMode::Preserve {
+            let mut dynamic_imports = DynamicImportRewriter {
+                mode: self.dynamic_import_mode,
+                esm_only_modules: &self.esm_only_modules,
+                has_used_interop_helper: false
+            };
+
+            updated_body.visit_mut_with(&mut dynamic_imports);
+
+            if dynamic_imports.has_used_interop_helper {
+                self.write_interop_helper("_interop_require_wildcard", &mut module_header);
+            }
+        }
+
+        if live_bindings.has_used_temporary {
+            module_header.helpers.push(ModuleItem::from(Stmt::Decl(Decl::Var(Box::new(
+                create_variable_declaration(
+                    VarDeclKind::Let,
+                    vec![
+                    create_variable_declarator(
+                        create_pat_ident(live_bindings.temporary_identifier.clone(), None),
+                        None
+                    )
+                    ]
+                )
+            )))));
+        }
+
+        if let (Some(_), TopLevelAwaitMode::Promise) = (top_level_await_span, self.top_level_await_mode) {
+            let mut async_body = std::mem::take(&mut module_header.hoisted_exports);
+            async_body.append(&mut updated_body);
+
+            let mut async_statements = Vec::new();
+
+            for node in async_body {
+                match node {
+                    ModuleItem::Stmt(statement) => async_statements.push(statement),
+                    module_declaration => updated_body.push(module_declaration)
                 }
             }
 
-            module.body = updated_body;
+            let async_module_function = Expr::Paren(ParenExpr {
+                span: DUMMY_SP,
+                expr: Box::new(create_arrow_expression(
+                    vec![],
+                    BlockStmtOrExpr::BlockStmt(create_block_statement(async_statements)),
+                    true
+                ))
+            });
+
+            updated_body.push(ModuleItem::from(define_export_assignment_by_literal_value(
+                "__tla",
+                Box::new(create_call_expression(Callee::Expr(Box::new(async_module_function)), vec![], None))
+            )));
+        }
+
+        let has_default_export_assignment = module_header.hoisted_exports
+            .iter()
+            .chain(&updated_body)
+            .any(|node| get_default_export_assignment_value(node).is_some());
+
+        if self.merge_named_exports && has_default_export_assignment {
+            module_header.helpers.push(ModuleItem::from(define_merge_named_exports_function()));
+        }
+
+        let mut module_body = module_header.into_module_items();
+
+        module_body.append(&mut updated_body);
+
+        if self.merge_named_exports {
+            module_body = module_body
+                .into_iter()
+                .flat_map(|node| match get_default_export_assignment_value(&node) {
+                    Some(default_value) => {
+                        if is_primitive_expression(default_value) {
+                            HANDLER.with(|handler| {
+                                handler
+                                    .struct_span_err(
+                                        default_value.span(),
+                                        "mergeNamedExports requires the default export to be an object or a function"
+                                    )
+                                    .emit()
+                            });
+                        }
+
+                        vec![
+                            ModuleItem::from(define_merged_module_exports_assignment(Box::new(default_value.clone()))),
+                            ModuleItem::from(define_module_exports_default_property())
+                        ]
+                    },
+                    None => vec![node]
+                })
+                .collect();
+        } else if self.add_module_exports && only_default_export {
+            module_body = module_body
+                .into_iter()
+                .flat_map(|node| match get_default_export_assignment_value(&node) {
+                    Some(default_value) => {
+                        let mut module_exports_items = vec![
+                            ModuleItem::from(define_module_exports_assignment(Box::new(default_value.clone())))
+                        ];
+
+                        // Primitives can't carry a `default` property.
+                        if !is_primitive_expression(default_value) {
+                            module_exports_items.push(ModuleItem::from(define_module_exports_default_property()));
+                        }
+
+                        module_exports_items
+                    },
+                    None => vec![node]
+                })
+                .collect();
+        }
+
+        module.body = module_body;
 
-            self.has_writed_all_module = old_has_writed_all_module;
-            self.has_writed_name_exports_module = old_has_writed_name_exports_module;
+        if self.verify {
+            let detected = CjsModuleLexer::detect(module);
+
+            HANDLER.with(|handler| {
+                for (export_name, span) in &expected_exports.export_names {
+                    if !detected.exports.contains(export_name) {
+                        handler
+                            .struct_span_warn(
+                                *span,
+                                &format!("cjs-module-lexer can't detect the export \"{}\", so it won't be a named import for ES modules", export_name)
+                            )
+                            .emit();
+                    }
+                }
+
+                for (source, span) in &expected_exports.reexport_sources {
+                    if !detected.reexports.contains(source) {
+                        handler
+                            .struct_span_warn(
+                                *span,
+                                &format!("cjs-module-lexer can't detect the re-export of \"{}\", so its names won't be named imports for ES modules", source)
+                            )
+                            .emit();
+                    }
+                }
+            });
         }
+
+        self.has_writed_all_module = old_has_writed_all_module;
+        self.has_writed_name_exports_module = old_has_writed_name_exports_module;
     }
+}
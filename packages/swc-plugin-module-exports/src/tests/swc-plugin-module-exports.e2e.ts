@@ -49,6 +49,30 @@ const createSut = (file_path: string) => {
 
 };
 
+const createSwcrcFile = (pluginOptions: Record<string, unknown> = {}, moduleOptions: Record<string, unknown> = {}) => {
+    const swcrc = {
+        $schema: 'https://json.schemastore.org/swcrc',
+        module: {
+            type: 'commonjs',
+            ...moduleOptions
+        },
+        jsc: {
+            target: 'es2015',
+            parser: {
+                syntax: 'typescript',
+                dts: true
+            },
+            experimental: {
+                plugins: [['@konekti/swc-plugin-module-exports', pluginOptions]]
+            }
+        }
+    };
+
+    fs.writeFileSync(join(getTestPath(), '.swcrc'), JSON.stringify(swcrc, null, 4), {
+        encoding: 'utf8'
+    });
+};
+
 beforeAll(() => {
     command('mkdir', '-p', `e2e/${packageName}`);
     command('bun', 'run', 'build', `${packageName}`);
@@ -64,24 +88,7 @@ beforeAll(() => {
     bun('add','@swc/core', '@swc/cli', '-D');
     bun('add', `../../packages/${packageName}`, '-D');
 
-    createSutFile('.swcrc')/*ts*/`
-        {
-            "$schema": "https://json.schemastore.org/swcrc",
-            "module": {
-                "type": "commonjs"
-            },
-            "jsc": {
-                "target": "es2015",
-                "parser": {
-                    "syntax": "typescript",
-                    "dts": true
-                },
-                "experimental": {
-                    "plugins": [["@konekti/swc-plugin-module-exports", {}]]
-                }
-            }
-        }
-    `;
+    createSwcrcFile();
 });
 
 afterAll(() => {
@@ -95,6 +102,8 @@ afterEach(() => {
     run('rm', '-rf', 'src');
     run('rm', '-rf', 'index.ts');
     run('rm', '-rf', 'sut.mjs');
+
+    createSwcrcFile();
 });
 
 test('should transpile named export and execute', () => {
@@ -171,3 +180,1078 @@ test('should transpile an export all declaration and execute', () => {
     run('bunx',' swc', '-d',' dist', 'src');
     expect(executeNode()).toBe(0);
 });
+
+test('should keep non export statements and execute', () => {
+    const run = commandSwcPluginModuleExports;
+
+    run('npm', 'pkg', 'set', 'type="module"');
+
+    createSutFile('src/source.ts')/*ts*/`
+        const prefix = 'sut';
+
+        function format(message: string): string {
+            return prefix + ' ' + message;
+        }
+
+        export function sutFunction(message: string): string {
+            return format(message);
+        };
+    `;
+
+    const executeNode = createSut('sut.mjs')/*ts*/`
+        import { sutFunction } from './dist/source.js';
+
+        if (sutFunction('message') !== 'sut message') process.exit(1);
+
+        process.exit(0);
+    `;
+
+    run('bunx',' swc', '-d',' dist', 'src');
+    expect(executeNode()).toBe(0);
+});
+
+test('should transpile exported destructuring declarations and execute', () => {
+    const run = commandSwcPluginModuleExports;
+
+    run('npm', 'pkg', 'set', 'type="module"');
+
+    createSutFile('src/source.ts')/*ts*/`
+        const sutObject = { sutA: 'sut', sutB: ['message', 1, 2] as const, sutD: undefined };
+
+        export const { sutA, sutB: [sutC, ...sutRest], sutD = 'sut default' } = sutObject;
+    `;
+
+    const executeNode = createSut('sut.mjs')/*ts*/`
+        import { sutA, sutC, sutRest, sutD } from './dist/source.js';
+
+        if (sutA !== 'sut' || sutC !== 'message') process.exit(1);
+        if (sutRest.length !== 2 || sutD !== 'sut default') process.exit(1);
+
+        process.exit(0);
+    `;
+
+    run('bunx',' swc', '-d',' dist', 'src');
+    expect(executeNode()).toBe(0);
+});
+
+test('should keep local bindings of exported variables and execute', () => {
+    const run = commandSwcPluginModuleExports;
+
+    run('npm', 'pkg', 'set', 'type="module"');
+
+    createSutFile('src/source.ts')/*ts*/`
+        export const sut_var: string = "sut message";
+
+        export const sut_upper: string = sut_var.toUpperCase();
+
+        export function sutFunction(): string {
+            return sut_var;
+        };
+    `;
+
+    const executeNode = createSut('sut.mjs')/*ts*/`
+        import { sut_upper, sutFunction } from './dist/source.js';
+
+        if (sut_upper !== 'SUT MESSAGE') process.exit(1);
+        if (sutFunction() !== 'sut message') process.exit(1);
+
+        process.exit(0);
+    `;
+
+    run('bunx',' swc', '-d',' dist', 'src');
+    expect(executeNode()).toBe(0);
+});
+
+test('should transpile import declarations to require and execute', () => {
+    const run = commandSwcPluginModuleExports;
+
+    run('npm', 'pkg', 'set', 'type="module"');
+
+    createSutFile('src/message.ts')/*ts*/`
+        export default function format(message: string): string {
+            return 'sut ' + message;
+        }
+        export const suffix: string = '!';
+    `;
+
+    createSutFile('src/source.ts')/*ts*/`
+        import format, { suffix } from './message';
+        import * as message from './message';
+        import { join } from 'path';
+
+        export function sutFunction(text: string): string {
+            return join(format(text) + suffix, message.suffix);
+        };
+    `;
+
+    const executeNode = createSut('sut.mjs')/*ts*/`
+        import { sutFunction } from './dist/source.js';
+
+        if (sutFunction('message') !== 'sut message!/!') process.exit(1);
+
+        process.exit(0);
+    `;
+
+    run('bunx',' swc', '-d',' dist', 'src');
+    expect(executeNode()).toBe(0);
+});
+
+test('should rewrite imported bindings to live member accesses and execute', () => {
+    const run = commandSwcPluginModuleExports;
+
+    run('npm', 'pkg', 'set', 'type="module"');
+
+    createSutFile('src/counter.ts')/*ts*/`
+        export let count: number = 0;
+
+        export function increment(): void {
+            count++;
+        };
+
+        export function getThis(this: unknown): unknown {
+            return this;
+        };
+
+        export function tag(strings: TemplateStringsArray, ...values: unknown[]): string {
+            return strings.join('') + values.length;
+        };
+    `;
+
+    createSutFile('src/source.ts')/*ts*/`
+        import { count, increment, getThis, tag } from './counter';
+
+        export function sutRead(): number {
+            return count;
+        };
+
+        export function sutIncrement(): number {
+            increment();
+
+            return (() => count)();
+        };
+
+        export function sutCallThis(): unknown {
+            return getThis();
+        };
+
+        export function sutTag(): string {
+            return tag\`sut\${1}message\`;
+        };
+
+        export function sutShadow(count: number): number {
+            return count;
+        };
+    `;
+
+    const executeNode = createSut('sut.mjs')/*ts*/`
+        import { sutRead, sutIncrement, sutCallThis, sutTag, sutShadow } from './dist/source.js';
+
+        if (sutIncrement() !== 1 || sutRead() !== 1) process.exit(1);
+        if (sutCallThis() !== undefined) process.exit(1);
+        if (sutTag() !== 'sutmessage1') process.exit(1);
+        if (sutShadow(5) !== 5) process.exit(1);
+
+        process.exit(0);
+    `;
+
+    run('bunx',' swc', '-d',' dist', 'src');
+    expect(executeNode()).toBe(0);
+});
+
+test('should transpile re-export specifiers and execute', () => {
+    const run = commandSwcPluginModuleExports;
+
+    run('npm', 'pkg', 'set', 'type="module"');
+
+    createSutFile('src/source.ts')/*ts*/`
+        export default function sutFunction(message: string): string {
+            return message;
+        };
+        export const sut_var: string = "sut message";
+    `;
+
+    createSutFile('src/index.ts')/*ts*/`
+        export { sut_var as sutVar, default as sutFunction } from './source';
+    `;
+
+    const executeNode = createSut('sut.mjs')/*ts*/`
+        import { sutFunction, sutVar } from './dist/index.js';
+
+        if (typeof sutFunction !== 'function') process.exit(1);
+        if (sutVar !== 'sut message') process.exit(1);
+
+        process.exit(0);
+    `;
+
+    run('bunx',' swc', '-d',' dist', 'src');
+    expect(executeNode()).toBe(0);
+});
+
+test('should transpile a namespace re-export and execute', () => {
+    const run = commandSwcPluginModuleExports;
+
+    run('npm', 'pkg', 'set', 'type="module"');
+
+    createSutFile('src/message.ts')/*ts*/`
+        export const sut_var: string = "sut message";
+    `;
+
+    createSutFile('src/index.ts')/*ts*/`
+        export * as sutNamespace from './message';
+    `;
+
+    const executeNode = createSut('sut.mjs')/*ts*/`
+        import { sutNamespace } from './dist/index.js';
+
+        if (sutNamespace.sut_var !== 'sut message') process.exit(1);
+
+        process.exit(0);
+    `;
+
+    run('bunx',' swc', '-d',' dist', 'src');
+    expect(executeNode()).toBe(0);
+});
+
+test('should hoist function exports for circular requires and execute', () => {
+    const run = commandSwcPluginModuleExports;
+
+    run('npm', 'pkg', 'set', 'type="module"');
+
+    createSutFile('src/source.ts')/*ts*/`
+        import { sutFunction } from './index';
+
+        export const sut_var: string = sutFunction("sut message");
+    `;
+
+    createSutFile('src/index.ts')/*ts*/`
+        export { sut_var as sutVar } from './source';
+
+        export function sutFunction(message: string): string {
+            return message;
+        };
+    `;
+
+    const executeNode = createSut('sut.mjs')/*ts*/`
+        import { sutFunction, sutVar } from './dist/index.js';
+
+        if (typeof sutFunction !== 'function') process.exit(1);
+        if (sutVar !== 'sut message') process.exit(1);
+
+        process.exit(0);
+    `;
+
+    run('bunx',' swc', '-d',' dist', 'src');
+    expect(executeNode()).toBe(0);
+});
+
+test('should export imported bindings as live getters for circular requires and execute', () => {
+    const run = commandSwcPluginModuleExports;
+
+    run('npm', 'pkg', 'set', 'type="module"');
+
+    createSutFile('src/source.ts')/*ts*/`
+        import sutDefault, { sutConst } from './index';
+
+        export { sutConst, sutDefault };
+    `;
+
+    createSutFile('src/index.ts')/*ts*/`
+        export { sutConst as sutReexported, sutDefault } from './source';
+
+        export const sutConst: string = "sut message";
+
+        export default function sutFunction(message: string): string {
+            return message;
+        };
+    `;
+
+    const executeNode = createSut('sut.mjs')/*ts*/`
+        import { sutReexported, sutDefault } from './dist/index.js';
+
+        if (sutReexported !== 'sut message') process.exit(1);
+        if (typeof sutDefault !== 'function') process.exit(1);
+
+        process.exit(0);
+    `;
+
+    run('bunx',' swc', '-d',' dist', 'src');
+    expect(executeNode()).toBe(0);
+});
+
+test('should update exported bindings on reassignment and execute', () => {
+    const run = commandSwcPluginModuleExports;
+
+    run('npm', 'pkg', 'set', 'type="module"');
+
+    createSutFile('src/source.ts')/*ts*/`
+        export let sutCounter: number = 0;
+        export let sutValue: string;
+
+        export function sutIncrement(): void {
+            sutCounter++;
+        };
+
+        export function sutAssign(value: number): void {
+            sutCounter = value;
+        };
+
+        export function sutIterate(values: number[]): void {
+            for (sutCounter of values);
+        };
+
+        export function sutSetValue(value: string): void {
+            sutValue = value;
+        };
+    `;
+
+    const executeNode = createSut('sut.mjs')/*ts*/`
+        import { createRequire } from 'module';
+
+        const source = createRequire(import.meta.url)('./dist/source.js');
+
+        if (source.sutCounter !== 0 || source.sutValue !== undefined) process.exit(1);
+
+        source.sutIncrement();
+        if (source.sutCounter !== 1) process.exit(1);
+
+        source.sutAssign(5);
+        if (source.sutCounter !== 5) process.exit(1);
+
+        source.sutIterate([6, 7]);
+        if (source.sutCounter !== 7) process.exit(1);
+
+        source.sutSetValue('sut message');
+        if (source.sutValue !== 'sut message') process.exit(1);
+
+        process.exit(0);
+    `;
+
+    run('bunx',' swc', '-d',' dist', 'src');
+    expect(executeNode()).toBe(0);
+});
+
+test('should export reassigned bindings as getters when configured and execute', () => {
+    const run = commandSwcPluginModuleExports;
+
+    run('npm', 'pkg', 'set', 'type="module"');
+
+    createSwcrcFile({ liveBindings: 'getter' });
+
+    createSutFile('src/source.ts')/*ts*/`
+        export let sutCounter: number = 0;
+
+        export function sutIncrement(): void {
+            sutCounter++;
+        };
+    `;
+
+    const executeNode = createSut('sut.mjs')/*ts*/`
+        import { createRequire } from 'module';
+
+        const source = createRequire(import.meta.url)('./dist/source.js');
+
+        source.sutIncrement();
+
+        if (source.sutCounter !== 1) process.exit(1);
+
+        process.exit(0);
+    `;
+
+    run('bunx',' swc', '-d',' dist', 'src');
+    expect(executeNode()).toBe(0);
+});
+
+test('should fail the build on invalid plugin options', () => {
+    const runAndCapture = createCommand({ cwd: join('e2e', packageName) });
+
+    createSutFile('src/source.ts')/*ts*/`
+        export const sut_var: string = "sut message";
+    `;
+
+    createSwcrcFile({ liveBinding: 'getter' });
+
+    const unknownKeyOutput = runAndCapture('bunx', 'swc', '-d', 'dist', 'src');
+
+    expect(unknownKeyOutput.status).not.toBe(0);
+    expect(unknownKeyOutput.stderr.toString()).toContain('unknown field `liveBinding`');
+
+    createSwcrcFile({ strictMode: 'yes' });
+
+    const wrongTypeOutput = runAndCapture('bunx', 'swc', '-d', 'dist', 'src');
+
+    expect(wrongTypeOutput.status).not.toBe(0);
+    expect(wrongTypeOutput.stderr.toString()).toContain('strictMode: invalid type');
+});
+
+test('should keep sloppy mode when strict mode is disabled and execute', () => {
+    const run = commandSwcPluginModuleExports;
+
+    run('npm', 'pkg', 'set', 'type="module"');
+
+    createSwcrcFile({ strictMode: false }, { strictMode: false });
+
+    createSutFile('src/source.ts')/*ts*/`
+        export function sutIsStrict(): boolean {
+            return this === undefined;
+        };
+    `;
+
+    const executeNode = createSut('sut.mjs')/*ts*/`
+        import { readFileSync } from 'fs';
+        import { createRequire } from 'module';
+
+        if (readFileSync('./dist/source.js', 'utf8').includes('"use strict"')) process.exit(1);
+
+        const source = createRequire(import.meta.url)('./dist/source.js');
+        const sutIsStrict = source.sutIsStrict;
+
+        if (sutIsStrict() !== false) process.exit(1);
+
+        process.exit(0);
+    `;
+
+    run('bunx',' swc', '-d',' dist', 'src');
+    expect(executeNode()).toBe(0);
+});
+
+test('should only flag modules with a default export as es modules when configured and execute', () => {
+    const run = commandSwcPluginModuleExports;
+
+    run('npm', 'pkg', 'set', 'type="module"');
+
+    createSwcrcFile({ esModule: 'only-when-default-export' });
+
+    createSutFile('src/named.ts')/*ts*/`
+        export const sut_var: string = "sut message";
+    `;
+
+    createSutFile('src/default.ts')/*ts*/`
+        export default "sut message";
+    `;
+
+    const executeNode = createSut('sut.mjs')/*ts*/`
+        import { createRequire } from 'module';
+
+        const require = createRequire(import.meta.url);
+
+        if (require('./dist/named.js').__esModule !== undefined) process.exit(1);
+        if (require('./dist/default.js').__esModule !== true) process.exit(1);
+
+        process.exit(0);
+    `;
+
+    run('bunx',' swc', '-d',' dist', 'src');
+    expect(executeNode()).toBe(0);
+});
+
+test('should assign a lone default export to module.exports when configured and execute', () => {
+    const run = commandSwcPluginModuleExports;
+
+    run('npm', 'pkg', 'set', 'type="module"');
+
+    createSwcrcFile({ addModuleExports: true });
+
+    createSutFile('src/source.ts')/*ts*/`
+        export default function sutFunction(message: string): string {
+            return message;
+        };
+    `;
+
+    const executeNode = createSut('sut.mjs')/*ts*/`
+        import { createRequire } from 'module';
+
+        const sutFunction = createRequire(import.meta.url)('./dist/source.js');
+
+        if (typeof sutFunction !== 'function') process.exit(1);
+        if (sutFunction.default !== sutFunction) process.exit(1);
+        if (sutFunction("sut message") !== 'sut message') process.exit(1);
+
+        process.exit(0);
+    `;
+
+    run('bunx',' swc', '-d',' dist', 'src');
+    expect(executeNode()).toBe(0);
+});
+
+test('should assign a re-exported default import to module.exports when configured and execute', () => {
+    const run = commandSwcPluginModuleExports;
+
+    run('npm', 'pkg', 'set', 'type="module"');
+
+    createSwcrcFile({ addModuleExports: true });
+
+    createSutFile('src/format.ts')/*ts*/`
+        export default function format(message: string): string {
+            return 'sut ' + message;
+        };
+    `;
+
+    createSutFile('src/index.ts')/*ts*/`
+        import format from './format';
+
+        export { format as default };
+    `;
+
+    const executeNode = createSut('sut.mjs')/*ts*/`
+        import { createRequire } from 'module';
+
+        const format = createRequire(import.meta.url)('./dist/index.js');
+
+        if (format('message') !== 'sut message' || format.default !== format) process.exit(1);
+
+        process.exit(0);
+    `;
+
+    run('bunx',' swc', '-d',' dist', 'src');
+    expect(executeNode()).toBe(0);
+});
+
+test('should merge named exports onto a callable default export when configured and execute', () => {
+    const run = commandSwcPluginModuleExports;
+
+    run('npm', 'pkg', 'set', 'type="module"');
+
+    createSwcrcFile({ mergeNamedExports: true });
+
+    createSutFile('src/source.ts')/*ts*/`
+        export class SutClass {
+            constructor(
+                public message: string
+            ) {}
+        };
+
+        export default function createSut(message: string): SutClass {
+            return new SutClass(message);
+        };
+    `;
+
+    const executeNode = createSut('sut.mjs')/*ts*/`
+        import { createRequire } from 'module';
+
+        const createSut = createRequire(import.meta.url)('./dist/source.js');
+
+        if (typeof createSut !== 'function') process.exit(1);
+        if (!(createSut("sut message") instanceof createSut.SutClass)) process.exit(1);
+        if (createSut.default !== createSut) process.exit(1);
+
+        process.exit(0);
+    `;
+
+    run('bunx',' swc', '-d',' dist', 'src');
+    expect(executeNode()).toBe(0);
+});
+
+test('should merge named exports onto a re-exported default import when configured and execute', () => {
+    const run = commandSwcPluginModuleExports;
+
+    run('npm', 'pkg', 'set', 'type="module"');
+
+    createSwcrcFile({ mergeNamedExports: true });
+
+    createSutFile('src/format.ts')/*ts*/`
+        export default function format(message: string): string {
+            return 'sut ' + message;
+        };
+    `;
+
+    createSutFile('src/index.ts')/*ts*/`
+        import format from './format';
+
+        export { format as default };
+        export const sut_var: string = "sut message";
+    `;
+
+    const executeNode = createSut('sut.mjs')/*ts*/`
+        import { createRequire } from 'module';
+
+        const format = createRequire(import.meta.url)('./dist/index.js');
+
+        if (format('message') !== 'sut message') process.exit(1);
+        if (format.sut_var !== 'sut message') process.exit(1);
+
+        process.exit(0);
+    `;
+
+    run('bunx',' swc', '-d',' dist', 'src');
+    expect(executeNode()).toBe(0);
+});
+
+test('should throw when a merged default export is a primitive at runtime', () => {
+    const run = commandSwcPluginModuleExports;
+
+    run('npm', 'pkg', 'set', 'type="module"');
+
+    createSwcrcFile({ mergeNamedExports: true });
+
+    createSutFile('src/source.ts')/*ts*/`
+        const sutValue: string = "sut message";
+
+        export const sut_var: string = "sut";
+        export default sutValue;
+    `;
+
+    const executeNode = createSut('sut.mjs')/*ts*/`
+        import { createRequire } from 'module';
+
+        try {
+            createRequire(import.meta.url)('./dist/source.js');
+        } catch (error) {
+            if (error instanceof TypeError && error.message.includes('mergeNamedExports')) process.exit(0);
+        }
+
+        process.exit(1);
+    `;
+
+    run('bunx',' swc', '-d',' dist', 'src');
+    expect(executeNode()).toBe(0);
+});
+
+test('should expose default object literal properties as named exports when configured and execute', () => {
+    const run = commandSwcPluginModuleExports;
+
+    run('npm', 'pkg', 'set', 'type="module"');
+
+    createSwcrcFile({ exportDefaultObjectProperties: true });
+
+    createSutFile('src/source.ts')/*ts*/`
+        function sutFunction(message: string): string {
+            return message;
+        };
+
+        export default { sutFunction, sut_var: "sut message" };
+    `;
+
+    const executeNode = createSut('sut.mjs')/*ts*/`
+        import source, { sutFunction, sut_var } from './dist/source.js';
+
+        if (typeof sutFunction !== 'function') process.exit(1);
+        if (sut_var !== 'sut message') process.exit(1);
+        if (source.default.sutFunction !== sutFunction) process.exit(1);
+
+        process.exit(0);
+    `;
+
+    run('bunx',' swc', '-d',' dist', 'src');
+    expect(executeNode()).toBe(0);
+});
+
+test('should expose default object literal properties alongside addModuleExports and execute', () => {
+    const run = commandSwcPluginModuleExports;
+
+    run('npm', 'pkg', 'set', 'type="module"');
+
+    createSwcrcFile({ exportDefaultObjectProperties: true, addModuleExports: true });
+
+    createSutFile('src/source.ts')/*ts*/`
+        function parse(text: string): string {
+            return 'sut ' + text;
+        };
+
+        export default { version: "1.0", parse };
+    `;
+
+    const executeNode = createSut('sut.mjs')/*ts*/`
+        import { createRequire } from 'module';
+        import { parse, version } from './dist/source.js';
+
+        const source = createRequire(import.meta.url)('./dist/source.js');
+
+        if (parse('message') !== 'sut message' || version !== '1.0') process.exit(1);
+        if (source.parse !== parse || source.default !== source) process.exit(1);
+
+        process.exit(0);
+    `;
+
+    run('bunx',' swc', '-d',' dist', 'src');
+    expect(executeNode()).toBe(0);
+});
+
+test('should transpile the "module.exports" export name to module.exports and execute', () => {
+    const run = commandSwcPluginModuleExports;
+
+    run('npm', 'pkg', 'set', 'type="module"');
+
+    createSutFile('src/source.ts')/*ts*/`
+        class SutClass {
+            message: string = "sut message";
+        };
+
+        export { SutClass as "module.exports" };
+    `;
+
+    const executeNode = createSut('sut.mjs')/*ts*/`
+        import { createRequire } from 'module';
+
+        const SutClass = createRequire(import.meta.url)('./dist/source.js');
+
+        if (typeof SutClass !== 'function') process.exit(1);
+        if (new SutClass().message !== 'sut message') process.exit(1);
+
+        process.exit(0);
+    `;
+
+    run('bunx',' swc', '-d',' dist', 'src');
+    expect(executeNode()).toBe(0);
+});
+
+test('should assign a re-exported import named "module.exports" to module.exports and execute', () => {
+    const run = commandSwcPluginModuleExports;
+
+    run('npm', 'pkg', 'set', 'type="module"');
+
+    createSutFile('src/client.ts')/*ts*/`
+        export class Client {
+            message: string = "sut message";
+        };
+    `;
+
+    createSutFile('src/index.ts')/*ts*/`
+        import { Client } from './client';
+
+        export { Client as "module.exports" };
+    `;
+
+    const executeNode = createSut('sut.mjs')/*ts*/`
+        import { createRequire } from 'module';
+
+        const Client = createRequire(import.meta.url)('./dist/index.js');
+
+        if (new Client().message !== 'sut message') process.exit(1);
+
+        process.exit(0);
+    `;
+
+    run('bunx',' swc', '-d',' dist', 'src');
+    expect(executeNode()).toBe(0);
+});
+
+test('should assign a namespace re-export named "module.exports" to module.exports and execute', () => {
+    const run = commandSwcPluginModuleExports;
+
+    run('npm', 'pkg', 'set', 'type="module"');
+
+    createSutFile('src/message.ts')/*ts*/`
+        export const sut_var: string = "sut message";
+    `;
+
+    createSutFile('src/index.ts')/*ts*/`
+        export * as "module.exports" from './message';
+    `;
+
+    createSutFile('src/other.ts')/*ts*/`
+        export * as default from './message';
+    `;
+
+    const executeNode = createSut('sut.mjs')/*ts*/`
+        import { createRequire } from 'module';
+
+        const require = createRequire(import.meta.url);
+
+        if (require('./dist/index.js').sut_var !== 'sut message') process.exit(1);
+        if (require('./dist/other.js').default.sut_var !== 'sut message') process.exit(1);
+
+        process.exit(0);
+    `;
+
+    run('bunx',' swc', '-d',' dist', 'src');
+    expect(executeNode()).toBe(0);
+});
+
+test('should transpile string export names and execute', () => {
+    const run = commandSwcPluginModuleExports;
+
+    run('npm', 'pkg', 'set', 'type="module"');
+
+    createSutFile('src/source.ts')/*ts*/`
+        const sut_var: string = "sut message";
+
+        export { sut_var as "sut-var", sut_var as class };
+    `;
+
+    const executeNode = createSut('sut.mjs')/*ts*/`
+        import * as source from './dist/source.js';
+
+        if (source['sut-var'] !== 'sut message') process.exit(1);
+        if (source.class !== 'sut message') process.exit(1);
+
+        process.exit(0);
+    `;
+
+    run('bunx',' swc', '-d',' dist', 'src');
+    expect(executeNode()).toBe(0);
+});
+
+test('should transpile typescript export forms and execute', () => {
+    const run = commandSwcPluginModuleExports;
+
+    run('npm', 'pkg', 'set', 'type="module"');
+
+    createSutFile('src/source.ts')/*ts*/`
+        export enum SutEnum { Message = "sut message" };
+        export namespace SutNamespace {
+            export const sut_var: string = "sut message";
+        };
+        export namespace SutTypes {
+            export type SutType = string;
+        };
+        export declare const sutAmbient: string;
+        declare function sutDeclared(): void;
+        export { sutDeclared };
+        export interface SutInterface {};
+        export type SutType = string;
+
+        export function sutFunction(message: string): string;
+        export function sutFunction(message: number): number;
+        export function sutFunction(message: any): any {
+            return message;
+        };
+    `;
+
+    const executeNode = createSut('sut.mjs')/*ts*/`
+        import * as source from './dist/source.js';
+
+        if (source.SutEnum.Message !== 'sut message') process.exit(1);
+        if (source.SutNamespace.sut_var !== 'sut message') process.exit(1);
+        if (typeof source.sutFunction !== 'function') process.exit(1);
+        if ('SutTypes' in source || 'sutAmbient' in source || 'sutDeclared' in source) process.exit(1);
+        if ('SutInterface' in source || 'SutType' in source) process.exit(1);
+
+        process.exit(0);
+    `;
+
+    run('bunx',' swc', '-d',' dist', 'src');
+    expect(executeNode()).toBe(0);
+});
+
+test('should transpile typescript export assignment and import require and execute', () => {
+    const run = commandSwcPluginModuleExports;
+
+    run('npm', 'pkg', 'set', 'type="module"');
+
+    createSutFile('src/source.ts')/*ts*/`
+        import path = require('path');
+
+        class SutClass {
+            getFileName(filePath: string): string {
+                return path.basename(filePath);
+            }
+        };
+
+        export = SutClass;
+    `;
+
+    const executeNode = createSut('sut.mjs')/*ts*/`
+        import { createRequire } from 'module';
+
+        const SutClass = createRequire(import.meta.url)('./dist/source.js');
+
+        if (typeof SutClass !== 'function') process.exit(1);
+        if (new SutClass().getFileName('/sut/message.ts') !== 'message.ts') process.exit(1);
+
+        process.exit(0);
+    `;
+
+    run('bunx',' swc', '-d',' dist', 'src');
+    expect(executeNode()).toBe(0);
+});
+
+test('should transpile import.meta properties and execute', () => {
+    const run = commandSwcPluginModuleExports;
+
+    run('npm', 'pkg', 'set', 'type="module"');
+
+    createSutFile('src/source.ts')/*ts*/`
+        export const sutUrl: string = import.meta.url;
+        export const sutDirname: string = import.meta.dirname;
+        export const sutFilename: string = import.meta.filename;
+        export const sutAsset: string = new URL('./asset.txt', import.meta.url).href;
+    `;
+
+    const executeNode = createSut('sut.mjs')/*ts*/`
+        import { fileURLToPath } from 'url';
+        import { sutUrl, sutDirname, sutFilename, sutAsset } from './dist/source.js';
+
+        const distDirname = fileURLToPath(new URL('./dist', import.meta.url));
+
+        if (sutUrl !== new URL('./dist/source.js', import.meta.url).href) process.exit(1);
+        if (sutDirname !== distDirname) process.exit(1);
+        if (sutFilename !== fileURLToPath(sutUrl)) process.exit(1);
+        if (sutAsset !== new URL('./dist/asset.txt', import.meta.url).href) process.exit(1);
+
+        process.exit(0);
+    `;
+
+    run('bunx',' swc', '-d',' dist', 'src');
+    expect(executeNode()).toBe(0);
+});
+
+test('should transpile dynamic import to require when configured and execute', () => {
+    const run = commandSwcPluginModuleExports;
+
+    run('npm', 'pkg', 'set', 'type="module"');
+
+    createSwcrcFile({ dynamicImport: 'require' });
+
+    createSutFile('src/source.ts')/*ts*/`
+        export const sut_var: string = "sut message";
+    `;
+
+    createSutFile('src/index.ts')/*ts*/`
+        export function loadSource() {
+            return import('./source.js');
+        };
+    `;
+
+    const executeNode = createSut('sut.mjs')/*ts*/`
+        import { readFileSync } from 'fs';
+        import { loadSource } from './dist/index.js';
+
+        if (readFileSync('./dist/index.js', 'utf8').includes('import(')) process.exit(1);
+
+        const source = await loadSource();
+
+        if (source.sut_var !== 'sut message') process.exit(1);
+
+        process.exit(0);
+    `;
+
+    run('bunx',' swc', '-d',' dist', 'src');
+    expect(executeNode()).toBe(0);
+});
+
+test('should transpile json imports with import attributes and execute', () => {
+    const run = commandSwcPluginModuleExports;
+
+    run('npm', 'pkg', 'set', 'type="module"');
+
+    createSutFile('src/data.json')/*ts*/`
+        { "message": "sut message" }
+    `;
+
+    createSutFile('src/index.ts')/*ts*/`
+        import data from './data.json' with { type: 'json' };
+        import { default as namedData } from './data.json' with { type: 'json' };
+
+        export { default as sutData } from './data.json' with { type: 'json' };
+        export const sut_var: string = data.message;
+        export const sut_named_var: string = namedData.message;
+    `;
+
+    const executeNode = createSut('sut.mjs')/*ts*/`
+        import { sutData, sut_var, sut_named_var } from './dist/index.js';
+
+        if (sutData.message !== 'sut message') process.exit(1);
+        if (sut_var !== 'sut message' || sut_named_var !== 'sut message') process.exit(1);
+
+        process.exit(0);
+    `;
+
+    run('bunx',' swc', '-d',' dist', '--copy-files', 'src');
+    expect(executeNode()).toBe(0);
+});
+
+test('should wrap top-level await in an async module when configured and execute', () => {
+    const run = commandSwcPluginModuleExports;
+
+    run('npm', 'pkg', 'set', 'type="module"');
+
+    createSwcrcFile({ topLevelAwait: 'promise' });
+
+    createSutFile('src/source.ts')/*ts*/`
+        export const sut_var: string = await Promise.resolve("sut message");
+    `;
+
+    const executeNode = createSut('sut.mjs')/*ts*/`
+        import { createRequire } from 'module';
+
+        const source = createRequire(import.meta.url)('./dist/source.js');
+
+        await source.__tla;
+
+        if (source.sut_var !== 'sut message') process.exit(1);
+
+        process.exit(0);
+    `;
+
+    run('bunx',' swc', '-d',' dist', 'src');
+    expect(executeNode()).toBe(0);
+});
+
+test('should fail the build when top-level await is wrapped in a module replacing module.exports', () => {
+    const runAndCapture = createCommand({ cwd: join('e2e', packageName) });
+
+    createSutFile('src/source.ts')/*ts*/`
+        export const sut_var: string = await Promise.resolve("sut message");
+
+        export default function format(message: string): string {
+            return message;
+        };
+    `;
+
+    createSwcrcFile({ topLevelAwait: 'promise', mergeNamedExports: true });
+
+    const mergeNamedExportsOutput = runAndCapture('bunx', 'swc', '-d', 'dist', 'src');
+
+    expect(mergeNamedExportsOutput.status).not.toBe(0);
+    expect(mergeNamedExportsOutput.stderr.toString()).toContain('can\'t replace module.exports in a module using top-level await');
+
+    createSutFile('src/source.ts')/*ts*/`
+        export default await Promise.resolve("sut message");
+    `;
+
+    createSwcrcFile({ topLevelAwait: 'promise', addModuleExports: true });
+
+    const addModuleExportsOutput = runAndCapture('bunx', 'swc', '-d', 'dist', 'src');
+
+    expect(addModuleExportsOutput.status).not.toBe(0);
+    expect(addModuleExportsOutput.stderr.toString()).toContain('can\'t replace module.exports in a module using top-level await');
+});
+
+test('should verify every export is detected by cjs-module-lexer and execute', () => {
+    const run = commandSwcPluginModuleExports;
+    const runAndCapture = createCommand({ cwd: join('e2e', packageName) });
+
+    run('npm', 'pkg', 'set', 'type="module"');
+
+    createSwcrcFile({
+        verify: true,
+        liveBindings: 'getter',
+        exportDefaultObjectProperties: true,
+        addModuleExports: true
+    });
+
+    createSutFile('src/parser.ts')/*ts*/`
+        function parse(text: string): string {
+            return 'sut ' + text;
+        };
+
+        export default { version: "1.0", parse };
+    `;
+
+    createSutFile('src/message.ts')/*ts*/`
+        export const message: string = "sut message";
+    `;
+
+    createSutFile('src/index.ts')/*ts*/`
+        export * from './message';
+        export { message as "sut-message" } from './message';
+        export let counter: number = 0;
+        export function increment() { counter++; }
+    `;
+
+    const executeNode = createSut('sut.mjs')/*ts*/`
+        import { message, "sut-message" as sutMessage, counter, increment } from './dist/index.js';
+        import { parse, version } from './dist/parser.js';
+
+        if (message !== 'sut message' || sutMessage !== 'sut message') process.exit(1);
+        if (counter !== 0 || typeof increment !== 'function') process.exit(1);
+        if (parse('message') !== 'sut message' || version !== '1.0') process.exit(1);
+
+        process.exit(0);
+    `;
+
+    const output = runAndCapture('bunx', 'swc', '-d', 'dist', 'src');
+
+    expect(output.stderr.toString()).not.toContain('cjs-module-lexer');
+    expect(executeNode()).toBe(0);
+});